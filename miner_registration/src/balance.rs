@@ -0,0 +1,178 @@
+//! Coldkey balance tracking.
+//!
+//! The free balance of the coldkey is pushed by the node through a subscription on
+//! `System::Account`, so an underfunded coldkey is noticed without polling and the
+//! registration resumes as soon as funds arrive.
+
+use crate::watch::StorageWatcher;
+use scale_value::Value;
+use subxt::backend::rpc::RpcClient;
+use subxt::ext::codec::Decode;
+use subxt::storage::DynamicAddress;
+use subxt::utils::{AccountId32, H256};
+use subxt::{OnlineClient, SubstrateConfig};
+
+/// `frame_system::AccountInfo` with the subtensor `u64` balance
+#[derive(Decode)]
+#[codec(crate = subxt::ext::codec)]
+struct AccountInfo {
+    _nonce: u32,
+    _consumers: u32,
+    _providers: u32,
+    _sufficients: u32,
+    data: AccountData,
+}
+
+/// `pallet_balances::AccountData` with the subtensor `u64` balance
+#[derive(Decode)]
+#[codec(crate = subxt::ext::codec)]
+struct AccountData {
+    free: u64,
+    _reserved: u64,
+    _frozen: u64,
+    _flags: u128,
+}
+
+/// A free balance change reported by the node
+#[derive(Debug, Clone, Copy)]
+pub struct BalanceUpdate {
+    /// Hash of the block the new balance was observed at
+    pub block_hash: H256,
+    /// The free balance in RAO
+    pub free_rao: u64,
+}
+
+/// Stream of free balance changes for a single account
+pub struct BalanceWatcher {
+    watcher: StorageWatcher,
+}
+
+impl BalanceWatcher {
+    /// Subscribes to changes of the `System::Account` storage entry for `account`
+    ///
+    /// The first update yielded by the watcher is the current balance.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - A reference to the blockchain client, used to build the storage key
+    /// * `rpc` - The RPC client to open the subscription on
+    /// * `account` - The account to watch
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `BalanceWatcher` if the subscription was opened, or an `Err` otherwise
+    pub async fn subscribe(
+        client: &OnlineClient<SubstrateConfig>,
+        rpc: &RpcClient,
+        account: &AccountId32,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let key = client.storage().address_bytes(&account_address(account))?;
+        let watcher = StorageWatcher::subscribe(rpc, key).await?;
+
+        Ok(Self { watcher })
+    }
+
+    /// Waits for the next free balance change
+    ///
+    /// # Returns
+    ///
+    /// `None` if the subscription ended, otherwise the next `BalanceUpdate` or an `Err` if the
+    /// notification could not be decoded. A missing account is reported as a zero balance.
+    pub async fn next(&mut self) -> Option<Result<BalanceUpdate, Box<dyn std::error::Error>>> {
+        let change = self.watcher.next().await?;
+        Some(change.map_err(Into::into).and_then(decode_balance_update))
+    }
+}
+
+/// Retrieves the free balance of `account` at the latest finalized block
+///
+/// # Arguments
+///
+/// * `client` - A reference to the blockchain client
+/// * `account` - The account to check
+///
+/// # Returns
+///
+/// A `Result` containing the free balance in RAO, or an `Err` if retrieval fails
+pub async fn get_free_balance(
+    client: &OnlineClient<SubstrateConfig>,
+    account: &AccountId32,
+) -> Result<u64, Box<dyn std::error::Error>> {
+    let key = client.storage().address_bytes(&account_address(account))?;
+    let data = client.storage().at_latest().await?.fetch_raw(key).await?;
+
+    Ok(decode_free_balance(data)?)
+}
+
+/// Decodes a raw `System::Account` change into a `BalanceUpdate`
+fn decode_balance_update(
+    (block_hash, data): (H256, Option<Vec<u8>>),
+) -> Result<BalanceUpdate, Box<dyn std::error::Error>> {
+    Ok(BalanceUpdate {
+        block_hash,
+        free_rao: decode_free_balance(data)?,
+    })
+}
+
+/// Decodes the free balance out of a raw `System::Account` value, treating a missing account as empty
+fn decode_free_balance(data: Option<Vec<u8>>) -> Result<u64, subxt::ext::codec::Error> {
+    match data {
+        Some(data) => Ok(AccountInfo::decode(&mut &data[..])?.data.free),
+        None => Ok(0),
+    }
+}
+
+/// Builds the dynamic storage address of `System::Account` for `account`
+fn account_address(account: &AccountId32) -> DynamicAddress<Vec<Value>> {
+    subxt::storage::dynamic("System", "Account", vec![Value::from_bytes(account.0)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use subxt::ext::scale_encode::EncodeAsType;
+    use subxt::Metadata;
+
+    /// Encodes `value` as the `System::Account` value type of the metadata
+    fn encode_account(value: Value) -> Vec<u8> {
+        let metadata = Metadata::decode(&mut &include_bytes!("../../metadata.scale")[..]).unwrap();
+        let ty = metadata
+            .pallet_by_name("System")
+            .and_then(|pallet| pallet.storage())
+            .and_then(|storage| storage.entry_by_name("Account"))
+            .unwrap()
+            .entry_type()
+            .value_ty();
+        value.encode_as_type(ty, metadata.types()).unwrap()
+    }
+
+    #[test]
+    fn free_balance_is_decoded_from_the_account_info() {
+        let data = Value::named_composite([
+            ("free", Value::u128(1_500_000_000)),
+            ("reserved", Value::u128(7)),
+            ("frozen", Value::u128(3)),
+            ("flags", Value::u128(1 << 127)),
+        ]);
+        let account = Value::named_composite([
+            ("nonce", Value::u128(12)),
+            ("consumers", Value::u128(1)),
+            ("providers", Value::u128(1)),
+            ("sufficients", Value::u128(0)),
+            ("data", data),
+        ]);
+
+        let free_rao = decode_free_balance(Some(encode_account(account))).unwrap();
+        assert_eq!(free_rao, 1_500_000_000);
+    }
+
+    #[test]
+    fn missing_account_has_no_balance() {
+        assert_eq!(decode_free_balance(None).unwrap(), 0);
+    }
+
+    #[test]
+    fn truncated_account_info_is_an_error() {
+        assert!(decode_free_balance(Some(vec![0; 16])).is_err());
+    }
+}
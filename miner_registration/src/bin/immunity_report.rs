@@ -0,0 +1,50 @@
+//! Prints the immunity and pruning risk of any hotkey on a subnet.
+
+use clap::Parser;
+use log::info;
+use shared::estimate_block_time;
+use shared::immunity::get_immunity_report;
+use std::str::FromStr;
+use subxt::utils::AccountId32;
+use subxt::{OnlineClient, SubstrateConfig};
+
+/// Struct to hold the report parameters, parsed from the command line
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct ImmunityReportParams {
+    /// SS58 address of the hotkey to report on
+    #[clap(long)]
+    hotkey: String,
+
+    #[clap(long)]
+    netuid: u16,
+
+    #[clap(long, default_value = "ws://127.0.0.1:9944")]
+    chain_endpoint: String,
+}
+
+/// Main function to run the immunity report
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    // Only the command line is read: the registration config.toml holds the hotkey's secret,
+    // not its address
+    let params = ImmunityReportParams::parse();
+    let hotkey = AccountId32::from_str(&params.hotkey)
+        .map_err(|e| format!("Invalid hotkey address {}: {}", params.hotkey, e))?;
+
+    let client = OnlineClient::<SubstrateConfig>::from_url(&params.chain_endpoint).await?;
+    info!("Estimating the block time, this takes about ten blocks...");
+    let block_time = estimate_block_time(&client).await?;
+
+    match get_immunity_report(&client, params.netuid, &hotkey, block_time).await? {
+        Some(report) => println!("{}", report),
+        None => println!(
+            "Hotkey {} is not registered on netuid {}",
+            hotkey, params.netuid
+        ),
+    }
+
+    Ok(())
+}
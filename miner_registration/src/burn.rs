@@ -0,0 +1,113 @@
+//! Burn price tracking for a subnet.
+//!
+//! Instead of querying `SubtensorModule::Burn` on every block, the watcher subscribes to
+//! the storage key and the node pushes a notification whenever the value changes.
+
+use crate::watch::StorageWatcher;
+use scale_value::Value;
+use subxt::backend::rpc::RpcClient;
+use subxt::ext::codec::Decode;
+use subxt::storage::DynamicAddress;
+use subxt::utils::H256;
+use subxt::{OnlineClient, SubstrateConfig};
+
+/// A burn price change reported by the node
+#[derive(Debug, Clone, Copy)]
+pub struct BurnUpdate {
+    /// Hash of the block the new price was observed at
+    pub block_hash: H256,
+    /// The burn price in RAO
+    pub burn_rao: u64,
+}
+
+/// Stream of burn price changes for a single netuid
+pub struct BurnWatcher {
+    watcher: StorageWatcher,
+}
+
+impl BurnWatcher {
+    /// Subscribes to changes of the `Burn` storage entry for `netuid`
+    ///
+    /// The first update yielded by the watcher is the current price.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - A reference to the blockchain client, used to build the storage key
+    /// * `rpc` - The RPC client to open the subscription on
+    /// * `netuid` - The network UID to watch
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `BurnWatcher` if the subscription was opened, or an `Err` otherwise
+    pub async fn subscribe(
+        client: &OnlineClient<SubstrateConfig>,
+        rpc: &RpcClient,
+        netuid: u16,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let key = client.storage().address_bytes(&burn_address(netuid))?;
+        let watcher = StorageWatcher::subscribe(rpc, key).await?;
+
+        Ok(Self { watcher })
+    }
+
+    /// Waits for the next burn price change
+    ///
+    /// # Returns
+    ///
+    /// `None` if the subscription ended, otherwise the next `BurnUpdate` or an `Err` if the
+    /// notification could not be decoded
+    pub async fn next(&mut self) -> Option<Result<BurnUpdate, Box<dyn std::error::Error>>> {
+        let change = self.watcher.next().await?;
+        Some(change.map_err(Into::into).and_then(decode_burn_update))
+    }
+}
+
+/// Decodes a raw `Burn` value into a `BurnUpdate`
+fn decode_burn_update(
+    (block_hash, data): (H256, Option<Vec<u8>>),
+) -> Result<BurnUpdate, Box<dyn std::error::Error>> {
+    let data = data.ok_or("Burn value not found for the given netuid")?;
+
+    Ok(BurnUpdate {
+        block_hash,
+        burn_rao: u64::decode(&mut &data[..])?,
+    })
+}
+
+/// Retrieves the recycle cost for a given network UID at a specific block
+///
+/// # Arguments
+///
+/// * `client` - A reference to the blockchain client
+/// * `netuid` - The network UID to check
+/// * `block_hash` - The hash of the block to read the storage at
+///
+/// # Returns
+///
+/// A `Result` containing the recycle cost as a `u64` if successful, or an `Err` if retrieval fails
+pub async fn get_recycle_cost_at(
+    client: &OnlineClient<SubstrateConfig>,
+    netuid: u16,
+    block_hash: H256,
+) -> Result<u64, Box<dyn std::error::Error>> {
+    let burn_cost: u64 = client
+        .storage()
+        .at(block_hash)
+        .fetch(&burn_address(netuid))
+        .await?
+        .ok_or_else(|| "Burn value not found for the given netuid".to_string())?
+        .as_type::<u64>()?;
+
+    Ok(burn_cost)
+}
+
+/// Builds the dynamic storage address of `SubtensorModule::Burn` for `netuid`
+fn burn_address(netuid: u16) -> DynamicAddress<Vec<Value>> {
+    subxt::storage::dynamic(
+        "SubtensorModule",
+        "Burn",
+        vec![Value::primitive(scale_value::Primitive::U128(
+            netuid as u128,
+        ))],
+    )
+}
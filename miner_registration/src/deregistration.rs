@@ -0,0 +1,97 @@
+//! Watching a registered hotkey for deregistration.
+//!
+//! Once registered, a hotkey can be pruned out of the subnet by a later registration. The watch
+//! follows the hotkey's `Uids` and `Keys` entries through storage subscriptions, survives dropped
+//! connections, and reports the deregistration through the log and an optional alert command.
+
+use crate::neuron::{Deregistration, MembershipWatcher};
+use crate::outcome::RegistrationOutcome;
+use log::{error, info, warn};
+use shared::resilient::{is_connection_error, ResilientClient};
+use tokio::process::Command;
+
+/// Watches a registered hotkey until it loses its UID
+///
+/// # Arguments
+///
+/// * `chain_endpoint` - The endpoint to follow the chain on
+/// * `registered` - The registration to watch
+///
+/// # Returns
+///
+/// A `Result` containing the `Deregistration`, or an `Err` if the storage could not be watched
+pub async fn watch_registration(
+    chain_endpoint: &str,
+    registered: &RegistrationOutcome,
+) -> Result<Deregistration, Box<dyn std::error::Error>> {
+    let connection = ResilientClient::connect(chain_endpoint).await?;
+    loop {
+        info!(
+            "👀 Watching hotkey {} with UID {} on netuid {} for deregistration",
+            registered.hotkey, registered.uid, registered.netuid
+        );
+        let watched = async {
+            let mut watcher = MembershipWatcher::subscribe(
+                &connection.client(),
+                &connection.rpc(),
+                registered.netuid,
+                registered.uid,
+                &registered.hotkey,
+            )
+            .await?;
+            watcher.wait_for_deregistration().await
+        };
+
+        match watched.await {
+            Err(e) if is_connection_error(e.as_ref()) => {
+                warn!("🔌 Lost the connection to {}: {}", connection.endpoint(), e);
+                connection.reconnect().await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Reports a deregistration through the log and `alert_command`, if set
+///
+/// The command runs through `sh -c` with the details in the `DEREGISTERED_NETUID`,
+/// `DEREGISTERED_UID`, `DEREGISTERED_HOTKEY`, `DEREGISTERED_BLOCK_HASH` and `REPLACED_BY`
+/// environment variables. A failing command is logged and does not stop the watch.
+///
+/// # Arguments
+///
+/// * `deregistration` - The deregistration to report
+/// * `alert_command` - The shell command to run, if any
+pub async fn alert(deregistration: &Deregistration, alert_command: Option<&str>) {
+    error!("🚨 Deregistered: {}", deregistration);
+
+    let Some(alert_command) = alert_command else {
+        return;
+    };
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(alert_command)
+        .env("DEREGISTERED_NETUID", deregistration.netuid.to_string())
+        .env("DEREGISTERED_UID", deregistration.uid.to_string())
+        .env("DEREGISTERED_HOTKEY", deregistration.hotkey.to_string())
+        .env(
+            "DEREGISTERED_BLOCK_HASH",
+            format!("{:?}", deregistration.block_hash),
+        )
+        .env(
+            "REPLACED_BY",
+            deregistration
+                .replaced_by
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_default(),
+        )
+        .status()
+        .await;
+
+    match status {
+        Ok(status) if status.success() => info!("📣 Alert command succeeded"),
+        Ok(status) => warn!("📣 Alert command exited with {}", status),
+        Err(e) => warn!("📣 Failed to run the alert command: {}", e),
+    }
+}
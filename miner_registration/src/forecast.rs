@@ -0,0 +1,303 @@
+//! Burn price forecasting for a subnet.
+//!
+//! Subtensor re-prices registrations once per adjustment interval, in the `on_initialize` of the
+//! first block where `current_block - LastAdjustmentBlock >= AdjustmentInterval`. This module
+//! reads the registration terms of a subnet and replays `upgraded_burn` with the runtime's
+//! `I110F18` fixed-point arithmetic to predict the burn after the next adjustment. The same
+//! terms carry the registration counters checked by `crate::quota`.
+
+use scale_value::Value;
+use std::fmt;
+use subxt::utils::H256;
+use subxt::{OnlineClient, SubstrateConfig};
+
+/// Number of fractional bits of the runtime's `I110F18`
+const FRAC_BITS: u32 = 18;
+
+/// The storage values that drive the burn adjustment of a subnet
+///
+/// Each field holds the `SubtensorModule` storage item of the same name.
+#[derive(Debug, Clone, Copy)]
+pub struct RegistrationTerms {
+    pub burn: u64,
+    pub min_burn: u64,
+    pub max_burn: u64,
+    pub adjustment_alpha: u64,
+    pub adjustment_interval: u16,
+    pub last_adjustment_block: u64,
+    pub target_registrations_per_interval: u16,
+    pub registrations_this_interval: u16,
+    pub pow_registrations_this_interval: u16,
+    pub burn_registrations_this_interval: u16,
+    pub registrations_this_block: u16,
+    pub max_registrations_per_block: u16,
+}
+
+/// Predicted burn price after the next adjustment
+#[derive(Debug, Clone, Copy)]
+pub struct BurnForecast {
+    /// The block the forecast was made at
+    pub current_block: u64,
+    /// The block whose `on_initialize` applies the next adjustment
+    pub adjustment_block: u64,
+    /// The burn price in RAO until the adjustment
+    pub current_burn_rao: u64,
+    /// The predicted burn price in RAO from the adjustment block on
+    pub next_burn_rao: u64,
+    /// Registrations counted towards the adjustment so far
+    pub registrations_this_interval: u16,
+    /// Registrations per interval at which the burn holds steady
+    pub target_registrations_per_interval: u16,
+}
+
+impl BurnForecast {
+    /// Whether the burn is predicted to be cheaper after the next adjustment
+    pub fn predicts_drop(&self) -> bool {
+        self.next_burn_rao < self.current_burn_rao
+    }
+
+    /// Number of blocks until the next adjustment
+    pub fn blocks_until_adjustment(&self) -> u64 {
+        self.adjustment_block.saturating_sub(self.current_block)
+    }
+}
+
+impl fmt::Display for BurnForecast {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "burn {:.9} TAO now, {:.9} TAO predicted at block {} (in {} blocks, {}/{} registrations this interval)",
+            self.current_burn_rao as f64 / 1e9,
+            self.next_burn_rao as f64 / 1e9,
+            self.adjustment_block,
+            self.blocks_until_adjustment(),
+            self.registrations_this_interval,
+            self.target_registrations_per_interval
+        )
+    }
+}
+
+impl RegistrationTerms {
+    /// Predicts the burn after the next adjustment, assuming no further registrations this interval
+    ///
+    /// # Arguments
+    ///
+    /// * `current_block` - The number of the block the terms were read at
+    ///
+    /// # Returns
+    ///
+    /// The `BurnForecast` for the next adjustment block
+    pub fn forecast(&self, current_block: u64) -> BurnForecast {
+        BurnForecast {
+            current_block,
+            adjustment_block: self.next_adjustment_block(current_block),
+            current_burn_rao: self.burn,
+            next_burn_rao: self.next_burn(),
+            registrations_this_interval: self.registrations_this_interval,
+            target_registrations_per_interval: self.target_registrations_per_interval,
+        }
+    }
+
+    /// The block whose `on_initialize` applies the next adjustment and resets the interval counters
+    ///
+    /// # Arguments
+    ///
+    /// * `current_block` - The number of the block the terms were read at
+    pub fn next_adjustment_block(&self, current_block: u64) -> u64 {
+        (self.last_adjustment_block + self.adjustment_interval as u64).max(current_block + 1)
+    }
+
+    /// Mirrors `adjust_registration_terms_for_networks`, which only re-prices the burn when burn
+    /// registrations dominate an oversubscribed interval, or PoW registrations dominate an
+    /// undersubscribed one (both when they are tied)
+    fn next_burn(&self) -> u64 {
+        let burn_adjusts =
+            if self.registrations_this_interval > self.target_registrations_per_interval {
+                self.burn_registrations_this_interval >= self.pow_registrations_this_interval
+            } else {
+                self.pow_registrations_this_interval >= self.burn_registrations_this_interval
+            };
+
+        if burn_adjusts {
+            self.upgraded_burn()
+        } else {
+            self.burn
+        }
+    }
+
+    /// Mirrors subtensor's `upgraded_burn`
+    ///
+    /// `next = alpha * burn + (1 - alpha) * burn * (registrations + target) / (2 * target)`,
+    /// clamped to `[MinBurn, MaxBurn]`, with `alpha = AdjustmentAlpha / u64::MAX`.
+    fn upgraded_burn(&self) -> u64 {
+        let target = self.target_registrations_per_interval as u128;
+        if target == 0 {
+            // The runtime cannot adjust without a target
+            return self.burn;
+        }
+
+        let current = to_fixed(self.burn as u128);
+        let updated = fixed_div(
+            fixed_mul(
+                current,
+                to_fixed(self.registrations_this_interval as u128 + target),
+            ),
+            to_fixed(2 * target),
+        );
+        let alpha = fixed_div(
+            to_fixed(self.adjustment_alpha as u128),
+            to_fixed(u64::MAX as u128),
+        );
+        let next = fixed_mul(alpha, current) + fixed_mul(to_fixed(1) - alpha, updated);
+
+        if next >= to_fixed(self.max_burn as u128) {
+            self.max_burn
+        } else if next <= to_fixed(self.min_burn as u128) {
+            self.min_burn
+        } else {
+            (next >> FRAC_BITS) as u64
+        }
+    }
+}
+
+/// Converts an integer to `I110F18` bits
+fn to_fixed(value: u128) -> u128 {
+    value << FRAC_BITS
+}
+
+/// Multiplies two `I110F18` values, truncating like the runtime
+fn fixed_mul(a: u128, b: u128) -> u128 {
+    (a * b) >> FRAC_BITS
+}
+
+/// Divides two `I110F18` values, truncating like the runtime
+fn fixed_div(a: u128, b: u128) -> u128 {
+    (a << FRAC_BITS) / b
+}
+
+/// Retrieves the registration terms of `netuid` at a specific block
+///
+/// # Arguments
+///
+/// * `client` - A reference to the blockchain client
+/// * `netuid` - The network UID to check
+/// * `block_hash` - The hash of the block to read the storage at
+///
+/// # Returns
+///
+/// A `Result` containing the `RegistrationTerms`, or an `Err` if retrieval fails
+pub async fn get_registration_terms(
+    client: &OnlineClient<SubstrateConfig>,
+    netuid: u16,
+    block_hash: H256,
+) -> Result<RegistrationTerms, Box<dyn std::error::Error>> {
+    let storage = client.storage().at(block_hash);
+    let fetch = |entry: &'static str| {
+        let storage = storage.clone();
+        async move {
+            let address = subxt::storage::dynamic(
+                "SubtensorModule",
+                entry,
+                vec![Value::u128(netuid as u128)],
+            );
+            storage.fetch_or_default(&address).await
+        }
+    };
+
+    Ok(RegistrationTerms {
+        burn: fetch("Burn").await?.as_type()?,
+        min_burn: fetch("MinBurn").await?.as_type()?,
+        max_burn: fetch("MaxBurn").await?.as_type()?,
+        adjustment_alpha: fetch("AdjustmentAlpha").await?.as_type()?,
+        adjustment_interval: fetch("AdjustmentInterval").await?.as_type()?,
+        last_adjustment_block: fetch("LastAdjustmentBlock").await?.as_type()?,
+        target_registrations_per_interval: fetch("TargetRegistrationsPerInterval")
+            .await?
+            .as_type()?,
+        registrations_this_interval: fetch("RegistrationsThisInterval").await?.as_type()?,
+        pow_registrations_this_interval: fetch("POWRegistrationsThisInterval").await?.as_type()?,
+        burn_registrations_this_interval: fetch("BurnRegistrationsThisInterval")
+            .await?
+            .as_type()?,
+        registrations_this_block: fetch("RegistrationsThisBlock").await?.as_type()?,
+        max_registrations_per_block: fetch("MaxRegistrationsPerBlock").await?.as_type()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `AdjustmentAlpha` of most subnets, about 0.97
+    const ALPHA: u64 = 17_893_341_751_498_265_066;
+
+    fn terms(burn: u64, registrations: u16, target: u16, alpha: u64) -> RegistrationTerms {
+        RegistrationTerms {
+            burn,
+            min_burn: 500_000,
+            max_burn: 100_000_000_000_000,
+            adjustment_alpha: alpha,
+            adjustment_interval: 360,
+            last_adjustment_block: 1_000,
+            target_registrations_per_interval: target,
+            registrations_this_interval: registrations,
+            pow_registrations_this_interval: 0,
+            burn_registrations_this_interval: registrations,
+            registrations_this_block: 0,
+            max_registrations_per_block: 1,
+        }
+    }
+
+    /// Expected values are the runtime's `upgraded_burn` evaluated with `substrate_fixed::I110F18`
+    #[test]
+    fn upgraded_burn_matches_the_runtime() {
+        let cases = [
+            (terms(1_000_000_000, 3, 1, ALPHA), 1_030_002_593),
+            (terms(2_500_000_001, 0, 3, ALPHA), 2_462_496_758),
+            (terms(7_777_777_777, 6, 3, 0), 11_666_666_665),
+            (terms(7_777_777_777, 6, 3, u64::MAX), 7_777_777_777),
+            (terms(90_000_000_000, 5, 1, ALPHA), 95_400_466_918),
+            (terms(123_456_789_012, 2, 1, u64::MAX / 2), 154_321_221_740),
+        ];
+        for (terms, expected) in cases {
+            assert_eq!(terms.upgraded_burn(), expected, "{:?}", terms);
+        }
+    }
+
+    #[test]
+    fn upgraded_burn_is_clamped() {
+        let mut low = terms(1_000_000_000, 0, 1, ALPHA);
+        low.min_burn = 990_000_000;
+        assert_eq!(low.upgraded_burn(), 990_000_000);
+
+        let mut high = terms(90_000_000_000, 5, 1, 0);
+        high.max_burn = 100_000_000_000;
+        assert_eq!(high.upgraded_burn(), 100_000_000_000);
+    }
+
+    #[test]
+    fn upgraded_burn_without_target_keeps_the_burn() {
+        assert_eq!(
+            terms(1_000_000_000, 3, 0, ALPHA).upgraded_burn(),
+            1_000_000_000
+        );
+    }
+
+    #[test]
+    fn next_burn_only_adjusts_when_the_dominant_kind_matches() {
+        // Oversubscribed by burn registrations
+        assert_eq!(terms(1_000_000_000, 3, 1, ALPHA).next_burn(), 1_030_002_593);
+
+        // Oversubscribed by PoW registrations
+        let mut pow = terms(1_000_000_000, 3, 1, ALPHA);
+        pow.pow_registrations_this_interval = 3;
+        pow.burn_registrations_this_interval = 0;
+        assert_eq!(pow.next_burn(), 1_000_000_000);
+
+        // Undersubscribed with burn registrations only
+        assert_eq!(terms(2_500_000_001, 2, 3, ALPHA).next_burn(), 2_500_000_001);
+
+        // Undersubscribed without registrations, the tie adjusts
+        assert_eq!(terms(2_500_000_001, 0, 3, ALPHA).next_burn(), 2_462_496_758);
+    }
+}
@@ -0,0 +1,126 @@
+//! Fork detection for the best-block subscription.
+//!
+//! Best blocks can be retracted: the node may announce a block at the same or a lower height,
+//! or one whose parent is not the previous best block. Decisions are always re-read at the new
+//! best block, so the tracker only has to notice the switch and report which block was dropped.
+
+use std::fmt;
+use subxt::utils::H256;
+
+/// A switch of the best chain away from the previously announced best block
+#[derive(Debug, Clone, Copy)]
+pub struct Reorg {
+    /// The best block that is no longer on the best chain
+    pub retracted_number: u64,
+    /// Hash of the retracted block
+    pub retracted_hash: H256,
+    /// The new best block
+    pub new_number: u64,
+    /// Hash of the new best block
+    pub new_hash: H256,
+}
+
+impl fmt::Display for Reorg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "block {} ({:?}) retracted, new best block {} ({:?})",
+            self.retracted_number, self.retracted_hash, self.new_number, self.new_hash
+        )
+    }
+}
+
+/// Remembers the last announced block to detect reorgs
+#[derive(Debug, Default)]
+pub struct ForkTracker {
+    last: Option<(u64, H256)>,
+}
+
+impl ForkTracker {
+    /// Records a newly announced block
+    ///
+    /// # Arguments
+    ///
+    /// * `number` - The number of the announced block
+    /// * `hash` - The hash of the announced block
+    /// * `parent_hash` - The hash of its parent
+    ///
+    /// # Returns
+    ///
+    /// `Some(Reorg)` if the block does not extend the previously announced one, `None` otherwise
+    pub fn observe(&mut self, number: u64, hash: H256, parent_hash: H256) -> Option<Reorg> {
+        let reorg = match self.last {
+            Some((last_number, last_hash))
+                if number <= last_number
+                    || (number == last_number + 1 && parent_hash != last_hash) =>
+            {
+                Some(Reorg {
+                    retracted_number: last_number,
+                    retracted_hash: last_hash,
+                    new_number: number,
+                    new_hash: hash,
+                })
+            }
+            _ => None,
+        };
+        self.last = Some((number, hash));
+
+        reorg
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(byte: u8) -> H256 {
+        H256::repeat_byte(byte)
+    }
+
+    #[test]
+    fn extending_the_best_block_is_not_a_reorg() {
+        let mut tracker = ForkTracker::default();
+        assert!(tracker.observe(10, hash(10), hash(9)).is_none());
+        assert!(tracker.observe(11, hash(11), hash(10)).is_none());
+        assert!(tracker.observe(12, hash(12), hash(11)).is_none());
+    }
+
+    #[test]
+    fn block_at_the_same_height_is_a_reorg() {
+        let mut tracker = ForkTracker::default();
+        tracker.observe(10, hash(10), hash(9));
+
+        let reorg = tracker.observe(10, hash(0xa0), hash(9)).unwrap();
+        assert_eq!(reorg.retracted_number, 10);
+        assert_eq!(reorg.retracted_hash, hash(10));
+        assert_eq!(reorg.new_number, 10);
+        assert_eq!(reorg.new_hash, hash(0xa0));
+
+        // The replacement is the new best block
+        assert!(tracker.observe(11, hash(11), hash(0xa0)).is_none());
+    }
+
+    #[test]
+    fn lower_block_is_a_reorg() {
+        let mut tracker = ForkTracker::default();
+        tracker.observe(10, hash(10), hash(9));
+        tracker.observe(11, hash(11), hash(10));
+
+        let reorg = tracker.observe(9, hash(0x90), hash(8)).unwrap();
+        assert_eq!(reorg.retracted_number, 11);
+        assert_eq!(reorg.retracted_hash, hash(11));
+        assert_eq!(reorg.new_number, 9);
+    }
+
+    #[test]
+    fn child_of_another_parent_is_a_reorg() {
+        let mut tracker = ForkTracker::default();
+        tracker.observe(10, hash(10), hash(9));
+
+        let reorg = tracker.observe(11, hash(0xb1), hash(0xb0)).unwrap();
+        assert_eq!(reorg.retracted_number, 10);
+        assert_eq!(reorg.retracted_hash, hash(10));
+        assert_eq!(reorg.new_number, 11);
+        assert_eq!(reorg.new_hash, hash(0xb1));
+    }
+}
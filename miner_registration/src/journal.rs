@@ -0,0 +1,487 @@
+//! Append-only journal of the registration loop.
+//!
+//! Every decision, every submitted extrinsic and every outcome is appended to a JSONL file, so a
+//! restarted bot knows what the previous run was doing. Submissions are written and synced
+//! before the extrinsic is broadcast: an extrinsic without a recorded outcome may still land, and
+//! is looked up on chain before the loop continues. A line cut short by a crash is dropped.
+
+use crate::presign::{PresignedExtrinsic, MORTALITY_BLOCKS};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use subxt::utils::{AccountId32, H256};
+
+/// A registration extrinsic that was handed to the broadcaster
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Submission {
+    pub time: DateTime<Utc>,
+    pub extrinsic_hash: H256,
+    pub nonce: u64,
+    pub tip_rao: u64,
+    pub netuid: u16,
+    pub hotkey: AccountId32,
+    pub coldkey: AccountId32,
+    /// The block the mortality is anchored at, the extrinsic cannot be in an earlier one
+    pub block_number: u64,
+    /// The first block the extrinsic is no longer valid in
+    pub valid_until: u64,
+}
+
+impl Submission {
+    /// Describes the submission of `extrinsic`
+    pub fn new(
+        extrinsic: &PresignedExtrinsic,
+        netuid: u16,
+        hotkey: &AccountId32,
+        coldkey: &AccountId32,
+    ) -> Self {
+        Self {
+            time: Utc::now(),
+            extrinsic_hash: extrinsic.hash,
+            nonce: extrinsic.nonce,
+            tip_rao: extrinsic.tip_rao,
+            netuid,
+            hotkey: hotkey.clone(),
+            coldkey: coldkey.clone(),
+            block_number: extrinsic.block_number,
+            valid_until: extrinsic.block_number + MORTALITY_BLOCKS,
+        }
+    }
+}
+
+/// How a submitted extrinsic ended
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutcomeStatus {
+    /// Included and the hotkey got a UID
+    Registered,
+    /// Rejected by the pool, or included with a failed dispatch
+    Failed,
+    /// Never included before its mortality ran out
+    Expired,
+    /// Never included, another extrinsic used its nonce
+    Replaced,
+}
+
+/// One line of the journal
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "entry", rename_all = "lowercase")]
+enum JournalEntry {
+    /// A run of the registration loop started
+    Started {
+        time: DateTime<Utc>,
+        netuid: u16,
+        hotkey: AccountId32,
+    },
+    /// The loop decided whether to attempt a registration
+    Decision {
+        time: DateTime<Utc>,
+        trigger: String,
+        attempt: bool,
+        reason: Option<String>,
+    },
+    Submitted(Submission),
+    /// The result of a submission
+    Outcome {
+        time: DateTime<Utc>,
+        extrinsic_hash: H256,
+        status: OutcomeStatus,
+        block_number: Option<u64>,
+        uid: Option<u16>,
+        error: Option<String>,
+    },
+}
+
+/// The loop state recovered from a previous run
+#[derive(Debug, Default)]
+pub struct Replayed {
+    /// Decisions taken for the hotkey
+    pub loops: u64,
+    /// Submissions for the hotkey that did not register it
+    pub failed_attempts: u32,
+    /// When the last outcome for the hotkey was recorded
+    pub last_attempt: Option<Instant>,
+}
+
+/// The JSONL journal file
+pub struct Journal {
+    file: File,
+    pending: Vec<Submission>,
+}
+
+impl Journal {
+    /// Opens the journal at `path` and replays it for `hotkey` on `netuid`
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The journal file, created if it does not exist
+    /// * `netuid` - The subnet of this run
+    /// * `hotkey` - The hotkey of this run
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Journal` and the state of the previous runs for the hotkey,
+    /// or an `Err` if the file cannot be read or is corrupted before its last line
+    pub fn open(
+        path: impl AsRef<Path>,
+        netuid: u16,
+        hotkey: &AccountId32,
+    ) -> Result<(Self, Replayed), Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e).into()),
+        };
+
+        let lines: Vec<&str> = contents.lines().collect();
+        let mut entries = Vec::with_capacity(lines.len());
+        // Length of the file without an incomplete last line
+        let mut complete_len = contents.len();
+        for (index, line) in lines.iter().enumerate() {
+            match serde_json::from_str::<JournalEntry>(line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) if index + 1 == lines.len() => {
+                    warn!(
+                        "📓 Dropping the incomplete last line of {}: {}",
+                        path.display(),
+                        e
+                    );
+                    complete_len = line.as_ptr() as usize - contents.as_ptr() as usize;
+                }
+                Err(e) => {
+                    return Err(
+                        format!("Invalid line {} in {}: {}", index + 1, path.display(), e).into(),
+                    )
+                }
+            }
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        // Cut off a line the previous run died halfway through, so it does not end up in the
+        // middle of the file, and start on a fresh line after a complete one
+        if complete_len < contents.len() {
+            file.set_len(complete_len as u64)?;
+        } else if !contents.is_empty() && !contents.ends_with('\n') {
+            writeln!(file)?;
+        }
+
+        let (pending, replayed) = replay(&entries, netuid, hotkey);
+        if !entries.is_empty() {
+            info!(
+                "📓 Replayed {} journal entries: {} decisions and {} failed attempts for this hotkey, {} submission(s) without an outcome",
+                entries.len(),
+                replayed.loops,
+                replayed.failed_attempts,
+                pending.len()
+            );
+        }
+
+        Ok((Self { file, pending }, replayed))
+    }
+
+    /// Submissions of this or earlier runs whose outcome is not known yet
+    pub fn pending(&self) -> &[Submission] {
+        &self.pending
+    }
+
+    /// Records the start of a run
+    pub fn started(
+        &mut self,
+        netuid: u16,
+        hotkey: &AccountId32,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.append(
+            &JournalEntry::Started {
+                time: Utc::now(),
+                netuid,
+                hotkey: hotkey.clone(),
+            },
+            false,
+        )
+    }
+
+    /// Records the decision taken on `trigger`, `skip_reason` is `None` for an attempt
+    pub fn decision(
+        &mut self,
+        trigger: &str,
+        skip_reason: Option<String>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.append(
+            &JournalEntry::Decision {
+                time: Utc::now(),
+                trigger: trigger.to_string(),
+                attempt: skip_reason.is_none(),
+                reason: skip_reason,
+            },
+            false,
+        )
+    }
+
+    /// Records a submission, synced to disk before the extrinsic is broadcast
+    pub fn submitted(&mut self, submission: Submission) -> Result<(), Box<dyn std::error::Error>> {
+        self.append(&JournalEntry::Submitted(submission.clone()), true)?;
+        self.pending.push(submission);
+        Ok(())
+    }
+
+    /// Records the outcome of the submission of `extrinsic_hash`
+    ///
+    /// # Arguments
+    ///
+    /// * `extrinsic_hash` - Hash of the submitted extrinsic
+    /// * `status` - How the submission ended
+    /// * `block_number` - The block that included the extrinsic, if it was included
+    /// * `uid` - The UID the hotkey got, if it was registered
+    /// * `error` - Why the attempt failed, if it did
+    ///
+    /// # Returns
+    ///
+    /// A `Result` which is `Ok` if the outcome was written, or an `Err` otherwise
+    pub fn outcome(
+        &mut self,
+        extrinsic_hash: H256,
+        status: OutcomeStatus,
+        block_number: Option<u64>,
+        uid: Option<u16>,
+        error: Option<String>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.append(
+            &JournalEntry::Outcome {
+                time: Utc::now(),
+                extrinsic_hash,
+                status,
+                block_number,
+                uid,
+                error,
+            },
+            true,
+        )?;
+        self.pending
+            .retain(|submission| submission.extrinsic_hash != extrinsic_hash);
+        Ok(())
+    }
+
+    /// Appends one line, syncing it to disk when `sync` is set
+    fn append(
+        &mut self,
+        entry: &JournalEntry,
+        sync: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.file, "{}", serde_json::to_string(entry)?)?;
+        if sync {
+            self.file.sync_data()?;
+        }
+        Ok(())
+    }
+}
+
+/// Finds the pending submissions and rebuilds the loop state of `hotkey` on `netuid`
+fn replay(
+    entries: &[JournalEntry],
+    netuid: u16,
+    hotkey: &AccountId32,
+) -> (Vec<Submission>, Replayed) {
+    let mut submissions: HashMap<H256, Submission> = HashMap::new();
+    let mut pending: Vec<H256> = Vec::new();
+    let mut replayed = Replayed::default();
+    let mut last_attempt: Option<DateTime<Utc>> = None;
+    let mut same_hotkey = false;
+
+    for entry in entries {
+        match entry {
+            JournalEntry::Started {
+                netuid: started_netuid,
+                hotkey: started_hotkey,
+                ..
+            } => same_hotkey = *started_netuid == netuid && started_hotkey == hotkey,
+            JournalEntry::Decision { .. } => {
+                if same_hotkey {
+                    replayed.loops += 1;
+                }
+            }
+            JournalEntry::Submitted(submission) => {
+                pending.push(submission.extrinsic_hash);
+                submissions.insert(submission.extrinsic_hash, submission.clone());
+            }
+            JournalEntry::Outcome {
+                time,
+                extrinsic_hash,
+                status,
+                ..
+            } => {
+                pending.retain(|hash| hash != extrinsic_hash);
+                let for_hotkey = submissions.get(extrinsic_hash).is_some_and(|submission| {
+                    submission.netuid == netuid && &submission.hotkey == hotkey
+                });
+                if for_hotkey {
+                    if *status != OutcomeStatus::Registered {
+                        replayed.failed_attempts += 1;
+                    }
+                    last_attempt = Some(*time);
+                }
+            }
+        }
+    }
+
+    // Map the wall clock time of the last outcome onto the monotonic clock for the rate limit
+    replayed.last_attempt = last_attempt.and_then(|time| {
+        let elapsed = (Utc::now() - time).to_std().unwrap_or(Duration::ZERO);
+        Instant::now().checked_sub(elapsed)
+    });
+
+    let pending = pending
+        .iter()
+        .filter_map(|hash| submissions.remove(hash))
+        .collect();
+    (pending, replayed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOTKEY: AccountId32 = AccountId32([1; 32]);
+    const OTHER_HOTKEY: AccountId32 = AccountId32([2; 32]);
+    const COLDKEY: AccountId32 = AccountId32([3; 32]);
+
+    fn started(netuid: u16, hotkey: AccountId32) -> JournalEntry {
+        JournalEntry::Started {
+            time: Utc::now(),
+            netuid,
+            hotkey,
+        }
+    }
+
+    fn decision() -> JournalEntry {
+        JournalEntry::Decision {
+            time: Utc::now(),
+            trigger: "block 100".to_string(),
+            attempt: true,
+            reason: None,
+        }
+    }
+
+    fn submitted(hash: u8, netuid: u16, hotkey: AccountId32) -> JournalEntry {
+        JournalEntry::Submitted(Submission {
+            time: Utc::now(),
+            extrinsic_hash: H256([hash; 32]),
+            nonce: hash.into(),
+            tip_rao: 0,
+            netuid,
+            hotkey,
+            coldkey: COLDKEY,
+            block_number: 100,
+            valid_until: 100 + MORTALITY_BLOCKS,
+        })
+    }
+
+    fn outcome(hash: u8, status: OutcomeStatus) -> JournalEntry {
+        JournalEntry::Outcome {
+            time: Utc::now(),
+            extrinsic_hash: H256([hash; 32]),
+            status,
+            block_number: None,
+            uid: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn empty_journal_replays_nothing() {
+        let (pending, replayed) = replay(&[], 1, &HOTKEY);
+        assert!(pending.is_empty());
+        assert_eq!(replayed.loops, 0);
+        assert_eq!(replayed.failed_attempts, 0);
+        assert!(replayed.last_attempt.is_none());
+    }
+
+    #[test]
+    fn replay_counts_the_hotkey_and_keeps_submissions_without_outcome() {
+        let entries = [
+            started(1, HOTKEY),
+            decision(),
+            submitted(1, 1, HOTKEY),
+            outcome(1, OutcomeStatus::Failed),
+            decision(),
+            submitted(2, 1, HOTKEY),
+            outcome(2, OutcomeStatus::Expired),
+            // Another hotkey's run is neither counted nor resumed here
+            started(1, OTHER_HOTKEY),
+            decision(),
+            submitted(3, 1, OTHER_HOTKEY),
+            outcome(3, OutcomeStatus::Failed),
+            submitted(4, 1, OTHER_HOTKEY),
+            // A later run of the hotkey that crashed after submitting
+            started(1, HOTKEY),
+            decision(),
+            submitted(5, 1, HOTKEY),
+        ];
+        let (pending, replayed) = replay(&entries, 1, &HOTKEY);
+
+        assert_eq!(replayed.loops, 3);
+        assert_eq!(replayed.failed_attempts, 2);
+        assert!(replayed.last_attempt.is_some());
+        // Submissions of any hotkey are looked up on chain, oldest first
+        let pending: Vec<H256> = pending.iter().map(|s| s.extrinsic_hash).collect();
+        assert_eq!(pending, [H256([4; 32]), H256([5; 32])]);
+    }
+
+    #[test]
+    fn registration_is_not_a_failed_attempt() {
+        let entries = [
+            started(1, HOTKEY),
+            decision(),
+            submitted(1, 1, HOTKEY),
+            outcome(1, OutcomeStatus::Registered),
+        ];
+        let (pending, replayed) = replay(&entries, 1, &HOTKEY);
+        assert!(pending.is_empty());
+        assert_eq!(replayed.failed_attempts, 0);
+        assert!(replayed.last_attempt.is_some());
+    }
+
+    #[test]
+    fn other_netuid_is_not_counted() {
+        let entries = [
+            started(2, HOTKEY),
+            decision(),
+            submitted(1, 2, HOTKEY),
+            outcome(1, OutcomeStatus::Failed),
+        ];
+        let (_, replayed) = replay(&entries, 1, &HOTKEY);
+        assert_eq!(replayed.loops, 0);
+        assert_eq!(replayed.failed_attempts, 0);
+        assert!(replayed.last_attempt.is_none());
+    }
+
+    #[test]
+    fn open_drops_a_truncated_last_line() {
+        let path = std::env::temp_dir().join(format!("journal-{}.jsonl", std::process::id()));
+        let lines: Vec<String> = [started(1, HOTKEY), decision(), submitted(1, 1, HOTKEY)]
+            .iter()
+            .map(|entry| serde_json::to_string(entry).unwrap())
+            .collect();
+        let truncated = &lines[2][..lines[2].len() / 2];
+        fs::write(&path, format!("{}\n{}\n{}", lines[0], lines[1], truncated)).unwrap();
+
+        let (journal, replayed) = Journal::open(&path, 1, &HOTKEY).unwrap();
+        assert_eq!(replayed.loops, 1);
+        assert!(journal.pending().is_empty());
+        drop(journal);
+
+        // The next entry starts on a fresh line, so the journal stays readable
+        let (mut journal, _) = Journal::open(&path, 1, &HOTKEY).unwrap();
+        journal.decision("block 101", None).unwrap();
+        drop(journal);
+        let (_, replayed) = Journal::open(&path, 1, &HOTKEY).unwrap();
+        assert_eq!(replayed.loops, 2);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
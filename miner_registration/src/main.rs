@@ -1,30 +1,95 @@
 //! This module implements a registration script for a blockchain network.
 //! It allows users to register hotkeys using provided coldkeys and other parameters.
 
-use clap::Parser;
+mod balance;
+mod burn;
+mod deregistration;
+mod forecast;
+mod fork;
+mod journal;
+mod neuron;
+mod outcome;
+mod pow;
+mod presign;
+mod quota;
+mod resume;
+mod watch;
+
+use crate::balance::{get_free_balance, BalanceWatcher};
+use crate::burn::{get_recycle_cost_at, BurnWatcher};
+use crate::deregistration::{alert, watch_registration};
+use crate::forecast::get_registration_terms;
+use crate::fork::ForkTracker;
+use crate::journal::{Journal, OutcomeStatus, Submission};
+use crate::neuron::get_uid;
+use crate::outcome::{find_neuron_registered, RegistrationExtrinsic, RegistrationOutcome};
+use crate::pow::{dry_run_pow, register_pow};
+use crate::presign::{sign_at, Presigner};
+use crate::quota::RegistrationQuota;
+use crate::resume::resolve_pending;
+use clap::{Parser, ValueEnum};
 use log::{error, info, warn};
-use scale_value::{Composite, Value};
-use serde::Deserialize;
-use shared::{estimate_block_time, parse_config};
-use sp_core::H256;
-use std::fs;
+use scale_value::Composite;
+use shared::broadcast::Broadcaster;
+use shared::budget::{Budget, Spend};
+use shared::errors::{DispatchErrorKind, Error, ErrorAction, ErrorPolicyParams};
+use shared::immunity::get_immunity_report;
+use shared::resilient::{is_connection_error, Backoff, ResilientClient};
+use shared::tx::{dry_run, estimate_fee, tao_to_rao, TipParams};
+use shared::wallet::{load_coldkey, load_hotkey};
+use shared::{parse_config, recent_block_time};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
+use subxt::backend::legacy::LegacyRpcMethods;
+use subxt::backend::rpc::RpcClient;
 use subxt::ext::sp_core::{sr25519, Pair};
 use subxt::tx::DefaultPayload;
+use subxt::utils::AccountId32;
 use subxt::{tx::PairSigner, OnlineClient, SubstrateConfig};
-use tokio::sync::Mutex;
-use std::str::FromStr;
+
+/// How the hotkey is registered on the subnet
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum RegistrationMode {
+    /// Pay the burn with `burned_register`
+    #[default]
+    Burn,
+    /// Solve the subnet's proof of work and submit `register`
+    Pow,
+}
+
+/// Which blocks drive the registration decisions
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum FollowMode {
+    /// React to every new best block, at the tip of the chain
+    Best,
+    /// React to finalized blocks only
+    #[default]
+    Finalized,
+}
 
 /// Struct to hold registration parameters, can be parsed from command line or config file
-#[derive(Parser, Deserialize, Debug)]
+#[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct RegistrationParams {
+    /// Mnemonic or seed of the coldkey, takes precedence over the wallet
+    #[clap(long)]
+    coldkey: Option<String>,
+
+    /// Mnemonic or seed of the hotkey, takes precedence over the wallet
+    #[clap(long)]
+    hotkey: Option<String>,
+
+    /// Name of the wallet to load the coldkey and hotkey from
     #[clap(long)]
-    coldkey: String,
+    wallet_name: Option<String>,
 
+    /// Name of the hotkey inside the wallet
     #[clap(long)]
-    hotkey: String,
+    wallet_hotkey: Option<String>,
+
+    /// Directory containing the wallets, defaults to ~/.bittensor/wallets
+    #[clap(long)]
+    wallet_path: Option<String>,
 
     #[clap(long)]
     netuid: u16,
@@ -32,10 +97,74 @@ struct RegistrationParams {
     #[clap(long)]
     max_cost_tao: String, // Changed to String to allow decimal input
 
+    /// Blocks that drive the registration decisions, success is always confirmed on finalized blocks
+    #[clap(long, value_enum, default_value_t = FollowMode::Finalized)]
+    follow: FollowMode,
+
+    /// Hold off while the burn is predicted to drop at the next adjustment
+    #[clap(long)]
+    wait_for_burn_drop: bool,
+
+    /// Registration mode for the netuid
+    #[clap(long, value_enum, default_value_t = RegistrationMode::Burn)]
+    registration_mode: RegistrationMode,
+
+    #[clap(flatten)]
+    tip: TipParams,
+
+    #[clap(flatten)]
+    on_error: ErrorPolicyParams,
+
+    /// Number of PoW solver threads, defaults to all CPU cores
+    #[clap(long)]
+    pow_threads: Option<usize>,
+
     #[clap(long, default_value = "ws://127.0.0.1:9944")]
     chain_endpoint: String,
+
+    /// Additional endpoints the signed registration extrinsic is broadcast to
+    #[clap(long, value_delimiter = ',')]
+    broadcast_endpoints: Vec<String>,
+
+    /// Total amount all attempts may spend on burns, fees and tips, in TAO
+    #[clap(long)]
+    total_budget_tao: Option<String>,
+
+    /// File the spend counted against `total_budget_tao` is saved to
+    #[clap(long, default_value = DEFAULT_BUDGET_FILE)]
+    budget_file: String,
+
+    /// Print the spend recorded in the budget file and the remaining budget, then exit
+    #[clap(long)]
+    budget_status: bool,
+
+    /// Append-only file recording every decision, submission and outcome, replayed on startup
+    #[clap(long, default_value = DEFAULT_JOURNAL_FILE)]
+    journal_file: String,
+
+    /// Keep running once the hotkey is registered and report when it is deregistered
+    #[clap(long)]
+    watch: bool,
+
+    /// Register the hotkey again when it is deregistered, implies `watch`
+    #[clap(long)]
+    reregister: bool,
+
+    /// Shell command run when the watched hotkey is deregistered
+    #[clap(long)]
+    alert_command: Option<String>,
+
+    /// Sign and validate the registration extrinsic without broadcasting it
+    #[clap(long)]
+    dry_run: bool,
 }
 
+/// Budget file used when `budget_file` is not set
+const DEFAULT_BUDGET_FILE: &str = "registration_budget.json";
+
+/// Journal file used when `journal_file` is not set
+const DEFAULT_JOURNAL_FILE: &str = "registration_journal.jsonl";
+
 /// Returns the current date and time in Eastern Time Zone
 ///
 /// # Returns
@@ -47,31 +176,139 @@ fn get_formatted_date_now() -> String {
     eastern_time.format("%Y-%m-%d %H:%M:%S %Z%z").to_string()
 }
 
+/// Registration loop state that survives reconnections, recovered from the journal on startup
+struct LoopState {
+    /// Number of loop iterations so far
+    loops: u64,
+    /// Number of registration attempts that failed, drives the tip escalation
+    failed_attempts: u32,
+    /// When the last failed attempt was rate limited
+    last_attempt: Instant,
+    /// Delays of the `backoff` error action, reset by any other failure
+    backoff: Backoff,
+    /// The total spend budget, if one is set
+    budget: Option<Budget>,
+    /// Journal of the decisions, submissions and outcomes
+    journal: Journal,
+}
+
 /// Attempts to register a hotkey on the blockchain
 ///
+/// A dropped connection does not end the registration: the client reconnects with exponential
+/// backoff and the loop resumes with its state intact. The state of previous runs is replayed
+/// from the journal.
+///
 /// # Arguments
 ///
 /// * `params` - A reference to `RegistrationParams` containing registration details
 ///
 /// # Returns
 ///
-/// A `Result` which is `Ok` if registration is successful, or an `Err` containing the error message
-// TODO: Parse event and decode Registered event
-async fn register_hotkey(params: &RegistrationParams) -> Result<(), Box<dyn std::error::Error>> {
-    // Initialize client connection to the blockchain
-    let client = Arc::new(OnlineClient::<SubstrateConfig>::from_url(&params.chain_endpoint).await?);
+/// A `Result` containing the `RegistrationOutcome` if registration is successful, `None` if a
+/// rejected attempt stopped the loop, or an `Err` containing the error message
+async fn register_hotkey(
+    params: &RegistrationParams,
+) -> Result<Option<RegistrationOutcome>, Box<dyn std::error::Error>> {
+    let (coldkey, hotkey) = load_keys(params)?;
+    let hotkey_account = AccountId32(hotkey.public().0);
+    let (mut journal, replayed) =
+        Journal::open(&params.journal_file, params.netuid, &hotkey_account)?;
+    journal.started(params.netuid, &hotkey_account)?;
 
-    // Parse coldkey and hotkey from provided strings
-    let coldkey: sr25519::Pair =
-        sr25519::Pair::from_string(&params.coldkey, None).map_err(|_| "Invalid coldkey")?;
-    let hotkey: sr25519::Pair =
-        sr25519::Pair::from_string(&params.hotkey, None).map_err(|_| "Invalid hotkey")?;
+    // Initialize client connection to the blockchain, sharing one RPC connection
+    let connection = ResilientClient::connect(&params.chain_endpoint).await?;
+    let mut state = LoopState {
+        loops: replayed.loops,
+        failed_attempts: replayed.failed_attempts,
+        last_attempt: replayed.last_attempt.unwrap_or_else(Instant::now),
+        backoff: params.on_error.backoff(),
+        budget: load_budget(params)?,
+        journal,
+    };
+
+    loop {
+        match run_registration(params, &connection, &coldkey, &hotkey, &mut state).await {
+            Err(e) if is_connection_error(e.as_ref()) => {
+                warn!("🔌 Lost the connection to {}: {}", connection.endpoint(), e);
+                connection.reconnect().await;
+            }
+            result => return result,
+        }
+    }
+}
 
+/// Runs the registration loop on the current connection
+///
+/// All subscriptions are created from the connection's current clients, so the loop can be
+/// started again after a reconnect.
+///
+/// # Arguments
+///
+/// * `params` - A reference to `RegistrationParams` containing registration details
+/// * `connection` - The connection to the chain
+/// * `coldkey` - The coldkey paying for the registration
+/// * `hotkey` - The hotkey to register
+/// * `state` - The loop state carried across reconnections
+///
+/// # Returns
+///
+/// A `Result` containing the `RegistrationOutcome` if registration is successful, `None` if a
+/// rejected attempt stopped the loop, or an `Err` if the loop failed, e.g. because the
+/// connection dropped
+async fn run_registration(
+    params: &RegistrationParams,
+    connection: &ResilientClient,
+    coldkey: &sr25519::Pair,
+    hotkey: &sr25519::Pair,
+    state: &mut LoopState,
+) -> Result<Option<RegistrationOutcome>, Box<dyn std::error::Error>> {
+    let rpc = connection.rpc();
+    let client = Arc::new(connection.client());
     let signer = Arc::new(PairSigner::new(coldkey.clone()));
+    let hotkey_account = AccountId32(hotkey.public().0);
+
+    // A submission of an earlier run may still land, so settle it before deciding anything
+    resolve_pending(&client, &rpc, &mut state.journal, state.budget.as_mut()).await?;
+
+    // Never burn TAO for a hotkey that already holds a UID on the subnet
+    if let Some(uid) = get_uid(&client, params.netuid, &hotkey_account).await? {
+        info!(
+            "✅ Hotkey {} is already registered on netuid {} with UID {}. Nothing to do.",
+            hotkey_account, params.netuid, uid
+        );
+        return Ok(Some(RegistrationOutcome::already_registered(
+            params.netuid,
+            uid,
+            hotkey_account,
+        )));
+    }
+
+    if params.registration_mode == RegistrationMode::Pow {
+        let threads = match params.pow_threads {
+            Some(threads) => threads,
+            None => std::thread::available_parallelism()?.get(),
+        };
+        let coldkey_account = AccountId32(coldkey.public().0);
+        return register_pow(&client, params.netuid, hotkey, &coldkey_account, threads)
+            .await
+            .map(Some);
+    }
 
-    let mut blocks = client.blocks().subscribe_finalized().await?;
-    let last_attempt = Arc::new(Mutex::new(Instant::now()));
-    let loops = Arc::new(Mutex::new(0u64));
+    let broadcaster = Broadcaster::connect(
+        &params.chain_endpoint,
+        client.as_ref().clone(),
+        &params.broadcast_endpoints,
+    )
+    .await?;
+    // Best blocks can be retracted, so every decision is re-read at the block that triggered it
+    let mut blocks = match params.follow {
+        FollowMode::Best => client.blocks().subscribe_best().await?,
+        FollowMode::Finalized => client.blocks().subscribe_finalized().await?,
+    };
+    let mut forks = ForkTracker::default();
+    let mut burn_watcher = BurnWatcher::subscribe(&client, &rpc, params.netuid).await?;
+    let coldkey_account = AccountId32(coldkey.public().0);
+    let mut balance_watcher = BalanceWatcher::subscribe(&client, &rpc, &coldkey_account).await?;
 
     // Cache the call_data for efficiency
     let call_data = Arc::new(Composite::named([
@@ -80,130 +317,558 @@ async fn register_hotkey(params: &RegistrationParams) -> Result<(), Box<dyn std:
     ]));
 
     // Convert max_cost_tao from TAO to RAO
-    let max_cost_rao: u64 = (f64::from_str(&params.max_cost_tao)? * 1e9).round() as u64;
+    let max_cost_rao: u64 = tao_to_rao(&params.max_cost_tao)?;
+    let tip_policy = params.tip.policy()?;
+
+    // The first notification of the burn subscription carries the current price
+    let mut recycle_cost_rao = burn_watcher
+        .next()
+        .await
+        .ok_or(Error::Disconnected(
+            "Burn subscription ended before reporting the current price".to_string(),
+        ))??
+        .burn_rao;
+    let mut free_balance_rao = balance_watcher
+        .next()
+        .await
+        .ok_or(Error::Disconnected(
+            "Balance subscription ended before reporting the current balance".to_string(),
+        ))??
+        .free_rao;
+
+    // Forecast the burn at the next adjustment and track the registration quota,
+    // both refreshed on every followed block
+    let latest_block = client.blocks().at_latest().await?;
+    let terms = get_registration_terms(&client, params.netuid, latest_block.hash()).await?;
+    let mut forecast = terms.forecast(latest_block.number().into());
+    let mut quota = RegistrationQuota::from_terms(&terms, latest_block.number().into());
+    let mut min_burn_rao = terms.min_burn;
+
+    // The fee does not depend on the burn, so it is estimated off the hot path
+    let mut fee_rao = estimate_burned_register_fee(&client, &call_data, &signer).await?;
+    info!(
+        "💰 Coldkey {} free balance: {:.9} TAO, estimated fee: {:.9} TAO",
+        coldkey_account,
+        free_balance_rao as f64 / 1e9,
+        fee_rao as f64 / 1e9
+    );
 
-    // Main registration loop
-    while let Some(block) = blocks.next().await {
-        let block = block?;
-        let block_number = block.header().number;
+    // Keep the next extrinsic signed ahead of time
+    let presigner = Presigner::spawn(
+        Arc::clone(&client),
+        Arc::clone(&signer),
+        Arc::clone(&call_data),
+        tip_policy.tip_rao(0),
+    );
+
+    // Main registration loop, woken up by new followed blocks, burn price and balance changes
+    loop {
+        let trigger = tokio::select! {
+            block = blocks.next() => {
+                let block = block.ok_or(Error::Disconnected("Block subscription ended".to_string()))??;
+                if let Some(reorg) = forks.observe(block.number().into(), block.hash(), block.header().parent_hash) {
+                    warn!("🔀 Chain reorganized: {}", reorg);
+                }
+                match get_registration_terms(&client, params.netuid, block.hash()).await {
+                    Ok(terms) => {
+                        forecast = terms.forecast(block.number().into());
+                        quota = RegistrationQuota::from_terms(&terms, block.number().into());
+                        min_burn_rao = terms.min_burn;
+                    }
+                    Err(e) => warn!("Failed to refresh the registration terms: {}", e),
+                }
+                format!("block {}", block.header().number)
+            }
+            update = burn_watcher.next() => {
+                let update = update.ok_or(Error::Disconnected("Burn subscription ended".to_string()))??;
+                recycle_cost_rao = update.burn_rao;
+                info!(
+                    "🔥 Recycle cost changed to {:.9} TAO at {:?}",
+                    update.burn_rao as f64 / 1e9,
+                    update.block_hash
+                );
+                format!("burn change at {:?}", update.block_hash)
+            }
+            update = balance_watcher.next() => {
+                let update = update.ok_or(Error::Disconnected("Balance subscription ended".to_string()))??;
+                free_balance_rao = update.free_rao;
+                info!(
+                    "💰 Coldkey free balance changed to {:.9} TAO at {:?}",
+                    update.free_rao as f64 / 1e9,
+                    update.block_hash
+                );
+                format!("balance change at {:?}", update.block_hash)
+            }
+        };
 
-        // Increment and log loop count
+        // Increment and log loop count, along with the quota and why the attempt is skipped
+        let exhausted = quota.exhausted();
         {
-            let mut loops_guard = loops.lock().await;
-            *loops_guard += 1;
-            info!(
-                "{} | {} | Attempting registration for block {}",
-                *loops_guard,
-                get_formatted_date_now(),
-                block_number
-            );
+            state.loops += 1;
+            match &exhausted {
+                Some(reason) => info!(
+                    "{} | {} | Skipping registration for {} | quota {} | {}",
+                    state.loops,
+                    get_formatted_date_now(),
+                    trigger,
+                    quota,
+                    reason
+                ),
+                None => info!(
+                    "{} | {} | Attempting registration for {} | quota {}",
+                    state.loops,
+                    get_formatted_date_now(),
+                    trigger,
+                    quota
+                ),
+            }
+            info!("🔮 Forecast: {}", forecast);
         }
 
-        // Check recycle cost
-        let recycle_cost_start = Instant::now();
-        let recycle_cost_rao = get_recycle_cost(&client, params.netuid).await?;
-        let recycle_cost_tao = recycle_cost_rao as f64 / 1e9;
-        let recycle_cost_duration = recycle_cost_start.elapsed();
-        info!("⏱️ get_recycle_cost took {:?}", recycle_cost_duration);
+        // The runtime would reject the registration with TooManyRegistrations*
+        if let Some(reason) = exhausted {
+            state.journal.decision(&trigger, Some(reason.to_string()))?;
+            continue;
+        }
+
+        // Stop once the total budget cannot pay for even the lowest possible burn
+        if let Some(budget) = &state.budget {
+            if budget.remaining_rao() < min_burn_rao.saturating_add(fee_rao) {
+                warn!(
+                    "💼 The remaining budget ({:.9} TAO) cannot cover the minimum burn ({:.9} TAO) plus fee ({:.9} TAO). Stopping.",
+                    budget.remaining_rao() as f64 / 1e9,
+                    min_burn_rao as f64 / 1e9,
+                    fee_rao as f64 / 1e9
+                );
+                state
+                    .journal
+                    .decision(&trigger, Some("total budget exhausted".to_string()))?;
+                return Ok(None);
+            }
+        }
+
+        // An attempt may spend up to max_cost_tao, and no more than is left of the total budget
+        let limit_rao = match &state.budget {
+            Some(budget) => max_cost_rao.min(budget.remaining_rao()),
+            None => max_cost_rao,
+        };
 
         // Skip if cost exceeds maximum allowed
-        if recycle_cost_rao > max_cost_rao {
+        let recycle_cost_tao = recycle_cost_rao as f64 / 1e9;
+        let base_cost_rao = recycle_cost_rao.saturating_add(fee_rao);
+        if base_cost_rao > limit_rao {
             warn!(
-                "💸 Recycle cost ({:.9} TAO) exceeds threshold ({} TAO). Skipping registration attempt.",
-                recycle_cost_tao, params.max_cost_tao
+                "💸 Recycle cost ({:.9} TAO) plus fee ({:.9} TAO) exceeds threshold ({:.9} TAO). Skipping registration attempt.",
+                recycle_cost_tao,
+                fee_rao as f64 / 1e9,
+                limit_rao as f64 / 1e9
             );
-            tokio::time::sleep(Duration::from_secs(1)).await;
+            state
+                .journal
+                .decision(&trigger, Some("cost exceeds threshold".to_string()))?;
             continue;
         }
 
-        // Prepare transaction payload
-        let payload = DefaultPayload::new(
-            "SubtensorModule",
-            "burned_register",
-            call_data.as_ref().clone(),
-        );
+        // The tip takes whatever is left of the budget once the burn and fee are paid
+        let policy_tip_rao = tip_policy.tip_rao(state.failed_attempts);
+        let tip_rao = tip_policy.clamped_tip_rao(state.failed_attempts, limit_rao - base_cost_rao);
+        if tip_rao < policy_tip_rao {
+            warn!(
+                "💸 Tip reduced from {:.9} TAO to {:.9} TAO to stay within the {:.9} TAO budget.",
+                policy_tip_rao as f64 / 1e9,
+                tip_rao as f64 / 1e9,
+                limit_rao as f64 / 1e9
+            );
+        }
+
+        // Paying now is wasteful when the next adjustment is predicted to be cheaper
+        if params.wait_for_burn_drop && forecast.predicts_drop() {
+            info!(
+                "⏳ Burn predicted to drop to {:.9} TAO in {} blocks. Waiting for the adjustment.",
+                forecast.next_burn_rao as f64 / 1e9,
+                forecast.blocks_until_adjustment()
+            );
+            state
+                .journal
+                .decision(&trigger, Some("waiting for the burn to drop".to_string()))?;
+            continue;
+        }
+
+        // Refuse to submit when the coldkey cannot pay for the burn, the fee and the tip
+        let total_cost_rao = base_cost_rao.saturating_add(tip_rao);
+        if free_balance_rao < total_cost_rao {
+            error!(
+                "🚫 Insufficient coldkey balance: {:.9} TAO free, {:.9} TAO needed ({:.9} TAO burn + {:.9} TAO fee + {:.9} TAO tip). Waiting for funds.",
+                free_balance_rao as f64 / 1e9,
+                total_cost_rao as f64 / 1e9,
+                recycle_cost_tao,
+                fee_rao as f64 / 1e9,
+                tip_rao as f64 / 1e9
+            );
+            state
+                .journal
+                .decision(&trigger, Some("insufficient balance".to_string()))?;
+            continue;
+        }
+        state.journal.decision(&trigger, None)?;
 
-        // Sign and submit the transaction
-        let sign_and_submit_start: Instant = Instant::now();
-        let client_clone: Arc<OnlineClient<SubstrateConfig>> = Arc::clone(&client);
-        let signer_clone: Arc<PairSigner<SubstrateConfig, sr25519::Pair>> = Arc::clone(&signer);
-        let result = tokio::spawn(async move {
-            client_clone
-                .tx()
-                .sign_and_submit_then_watch(&payload, &*signer_clone, Default::default())
-                .await
-        })
-        .await??;
-        let sign_and_submit_duration = sign_and_submit_start.elapsed();
-        info!("⏱️ sign_and_submit took {:?}", sign_and_submit_duration);
-
-        // Wait for transaction finalization
-        let finalization_start = Instant::now();
-        match result.wait_for_finalized_success().await {
-            Ok(events) => {
-                let finalization_duration = finalization_start.elapsed();
+        // Take the presigned extrinsic, the same bytes go to every endpoint. Only sign on the
+        // critical path when the tip changed since it was signed.
+        let prepare_start: Instant = Instant::now();
+        presigner.set_tip(tip_rao).await;
+        let extrinsic = match presigner.get(tip_rao).await {
+            Some(presigned) => {
                 info!(
-                    "⏱️ wait_for_finalized_success took {:?}",
-                    finalization_duration
+                    "✍️ Using extrinsic {:?} presigned at block {} (nonce {}, signed {:?} ago in {:?})",
+                    presigned.hash,
+                    presigned.block_number,
+                    presigned.nonce,
+                    presigned.signed_at.elapsed(),
+                    presigned.sign_duration
+                );
+                presigned
+            }
+            None => {
+                warn!(
+                    "No extrinsic presigned with a {:.9} TAO tip, signing on the critical path.",
+                    tip_rao as f64 / 1e9
                 );
-                let block_hash: H256 = events.extrinsic_hash();
+                let payload = DefaultPayload::new(
+                    "SubtensorModule",
+                    "burned_register",
+                    call_data.as_ref().clone(),
+                );
+                // Anchored at the best block, like the presigned extrinsics
+                let header = LegacyRpcMethods::<SubstrateConfig>::new(rpc.clone())
+                    .chain_get_header(None)
+                    .await?
+                    .ok_or("Best block header not found")?;
+                sign_at(&client, &signer, &payload, tip_rao, &header)
+                    .await
+                    .map_err(|e| e as Box<dyn std::error::Error>)?
+            }
+        };
+        // Written before the broadcast, so a crash cannot lose an extrinsic that may still land
+        state.journal.submitted(Submission::new(
+            &extrinsic,
+            params.netuid,
+            &hotkey_account,
+            &coldkey_account,
+        ))?;
+        let prepare_duration = prepare_start.elapsed();
+
+        // Broadcast, follow the first endpoint to report the extrinsic in a block, and wait for
+        // transaction finalization
+        let submit_start = Instant::now();
+        let finalized = async {
+            let broadcast = broadcaster.submit_and_watch(&extrinsic.encoded).await?;
+            let in_block_duration = submit_start.elapsed();
+            info!(
+                "📡 Extrinsic {:?} in block {:?}, first reported by {}",
+                broadcast.extrinsic_hash,
+                broadcast.in_block.block_hash(),
+                broadcast.endpoint
+            );
+            let in_block = broadcast.wait_for_finalized().await?;
+            let finalized_duration = submit_start.elapsed() - in_block_duration;
+            // A failed dispatch still pays the fee and the tip, so the spend is counted first
+            if let Some(budget) = state.budget.as_mut() {
+                let spend = Spend::from_events(&in_block.fetch_events().await?, &coldkey_account)?;
+                budget.record(spend)?;
+            }
+            let events = in_block.wait_for_success().await?;
+            info!(
+                "⏱️ prepare {:?} | submit to in block {:?} | in block to finalized {:?} | events {:?} (tip {:.9} TAO)",
+                prepare_duration,
+                in_block_duration,
+                finalized_duration,
+                submit_start.elapsed() - in_block_duration - finalized_duration,
+                tip_rao as f64 / 1e9
+            );
+            Ok::<_, Box<dyn std::error::Error>>((in_block.block_hash(), events))
+        };
+        let (kind, action, message) = match finalized.await {
+            Ok((block_hash, events)) => {
+                let registered = find_neuron_registered(&events, params.netuid, &hotkey_account)?;
+                let block_number = client.blocks().at(block_hash).await?.number();
+                let burn_paid_rao = get_recycle_cost_at(&client, params.netuid, block_hash).await?;
                 info!(
-                    "🎯 Registration successful at block {}. Events: {:?}",
-                    block_hash, events
+                    "🎯 Registration successful at block {} ({:?}) with UID {}",
+                    block_number, block_hash, registered.1
                 );
-                break; // Exit the loop on successful registration
+                state.journal.outcome(
+                    extrinsic.hash,
+                    OutcomeStatus::Registered,
+                    Some(block_number.into()),
+                    Some(registered.1),
+                    None,
+                )?;
+
+                return Ok(Some(RegistrationOutcome {
+                    netuid: registered.0,
+                    uid: registered.1,
+                    hotkey: registered.2,
+                    extrinsic: Some(RegistrationExtrinsic {
+                        block_number,
+                        block_hash,
+                        extrinsic_hash: events.extrinsic_hash(),
+                        burn_paid_rao,
+                        tip_rao,
+                    }),
+                }));
             }
+            Err(e) if is_connection_error(e.as_ref()) => return Err(e),
             Err(e) => {
-                error!("Registration failed: {:?}", e);
-                // Continue to next iteration
+                let kind = DispatchErrorKind::classify(e.as_ref());
+                let action = params.on_error.action(kind);
+                error!(
+                    "Registration failed after {:?} ({}, {}): {}",
+                    submit_start.elapsed(),
+                    kind,
+                    action,
+                    e
+                );
+                state.journal.outcome(
+                    extrinsic.hash,
+                    OutcomeStatus::Failed,
+                    None,
+                    None,
+                    Some(e.to_string()),
+                )?;
+                state.failed_attempts += 1;
+                // The attempt may have consumed the presigned nonce
+                presigner.invalidate().await;
+                (kind, action, e.to_string())
+            }
+        };
+
+        // An earlier attempt may have landed even though this one failed
+        if let Some(uid) = get_uid(&client, params.netuid, &hotkey_account).await? {
+            info!(
+                "✅ Hotkey {} was registered on netuid {} with UID {} by an earlier attempt.",
+                hotkey_account, params.netuid, uid
+            );
+            return Ok(Some(RegistrationOutcome::already_registered(
+                params.netuid,
+                uid,
+                hotkey_account,
+            )));
+        }
+
+        match action {
+            ErrorAction::Stop => {
+                warn!("🛑 Stopping after a rejected registration ({}).", kind);
+                return Ok(None);
+            }
+            ErrorAction::Fail => return Err(Error::Dispatch(kind, message).into()),
+            ErrorAction::Backoff => {
+                let delay = state.backoff.next_delay();
+                warn!(
+                    "⏳ Backing off for {:?} after a rejected registration ({}).",
+                    delay, kind
+                );
+                tokio::time::sleep(delay).await;
             }
+            ErrorAction::Retry => state.backoff = params.on_error.backoff(),
+        }
+
+        // Refresh the fee estimate while waiting for the next attempt
+        match estimate_burned_register_fee(&client, &call_data, &signer).await {
+            Ok(fee) => fee_rao = fee,
+            Err(e) => warn!("Failed to refresh the fee estimate: {}", e),
         }
 
         // Implement rate limiting
-        let mut last_attempt_guard = last_attempt.lock().await;
-        if last_attempt_guard.elapsed() < Duration::from_secs(12) {
-            tokio::time::sleep(Duration::from_secs(12) - last_attempt_guard.elapsed()).await;
+        if state.last_attempt.elapsed() < Duration::from_secs(12) {
+            tokio::time::sleep(Duration::from_secs(12) - state.last_attempt.elapsed()).await;
+        }
+        state.last_attempt = Instant::now();
+    }
+}
+
+/// Signs a `burned_register` extrinsic and validates it against the chain without broadcasting it
+///
+/// Reports the current recycle cost, the coldkey balance, the estimated fee and the nonce. With
+/// `registration_mode = "pow"` the `register` extrinsic is checked instead.
+///
+/// # Arguments
+///
+/// * `params` - A reference to `RegistrationParams` containing registration details
+///
+/// # Returns
+///
+/// A `Result` which is `Ok` if the extrinsic could be built and validated, or an `Err` otherwise
+async fn dry_run_hotkey(params: &RegistrationParams) -> Result<(), Box<dyn std::error::Error>> {
+    let rpc = RpcClient::from_url(&params.chain_endpoint).await?;
+    let client = OnlineClient::<SubstrateConfig>::from_rpc_client(rpc.clone()).await?;
+
+    let (coldkey, hotkey) = load_keys(params)?;
+    let signer = PairSigner::new(coldkey.clone());
+    let coldkey_account = AccountId32(coldkey.public().0);
+    let hotkey_account = AccountId32(hotkey.public().0);
+
+    if let Some(uid) = get_uid(&client, params.netuid, &hotkey_account).await? {
+        warn!(
+            "Hotkey {} is already registered on netuid {} with UID {}; a real run would not submit.",
+            hotkey_account, params.netuid, uid
+        );
+    }
+
+    if params.registration_mode == RegistrationMode::Pow {
+        return dry_run_pow(&client, &rpc, params.netuid, &hotkey, &coldkey_account).await;
+    }
+
+    let latest_block = client.blocks().at_latest().await?;
+    let recycle_cost_rao = get_recycle_cost_at(&client, params.netuid, latest_block.hash()).await?;
+    let free_balance_rao = get_free_balance(&client, &coldkey_account).await?;
+    let terms = get_registration_terms(&client, params.netuid, latest_block.hash()).await?;
+    let forecast = terms.forecast(latest_block.number().into());
+    let quota = RegistrationQuota::from_terms(&terms, latest_block.number().into());
+    let max_cost_rao: u64 = tao_to_rao(&params.max_cost_tao)?;
+    let tip_rao = params.tip.policy()?.tip_rao(0);
+
+    let payload = DefaultPayload::new(
+        "SubtensorModule",
+        "burned_register",
+        Composite::named([
+            ("netuid", params.netuid.into()),
+            ("hotkey", hotkey.public().0.to_vec().into()),
+        ]),
+    );
+    let report = dry_run(&client, &rpc, &payload, &signer, tip_rao).await?;
+    let budget = load_budget(params)?;
+    let total_cost_rao = recycle_cost_rao
+        .saturating_add(report.fee_rao)
+        .saturating_add(tip_rao);
+
+    println!("Dry run of burned_register on netuid {}:", params.netuid);
+    println!("Hotkey: {}", hotkey_account);
+    println!("Coldkey: {}", coldkey_account);
+    println!(
+        "Recycle cost: {:.9} TAO, {:.9} TAO with fee and tip (max {} TAO{})",
+        recycle_cost_rao as f64 / 1e9,
+        total_cost_rao as f64 / 1e9,
+        params.max_cost_tao,
+        if total_cost_rao > max_cost_rao {
+            ", exceeded"
+        } else {
+            ""
+        }
+    );
+    println!("Burn forecast: {}", forecast);
+    match quota.exhausted() {
+        Some(reason) => println!("Registration quota: {}, {}", quota, reason),
+        None => println!("Registration quota: {}", quota),
+    }
+    println!(
+        "Free balance: {:.9} TAO{}",
+        free_balance_rao as f64 / 1e9,
+        if free_balance_rao < total_cost_rao {
+            ", insufficient for burn, fee and tip"
+        } else {
+            ""
         }
-        *last_attempt_guard = Instant::now();
+    );
+    if let Some(budget) = budget {
+        println!("{}", budget);
     }
+    println!("{}", report);
 
     Ok(())
 }
 
-/// Retrieves the current recycle cost for a given network UID
+/// Loads the total spend budget if `total_budget_tao` is set
+///
+/// # Arguments
+///
+/// * `params` - A reference to `RegistrationParams` containing the budget settings
+///
+/// # Returns
+///
+/// A `Result` containing the `Budget`, `None` if no budget is set, or an `Err` if the budget
+/// file cannot be read
+fn load_budget(params: &RegistrationParams) -> Result<Option<Budget>, Box<dyn std::error::Error>> {
+    params
+        .total_budget_tao
+        .as_deref()
+        .map(|tao| Budget::load(&params.budget_file, tao_to_rao(tao)?))
+        .transpose()
+}
+
+/// Loads the coldkey and hotkey from the provided secrets or the wallet
+///
+/// # Arguments
+///
+/// * `params` - A reference to `RegistrationParams` containing the key sources
+///
+/// # Returns
+///
+/// A `Result` containing the coldkey and hotkey pairs, or an `Err` if either cannot be loaded
+fn load_keys(
+    params: &RegistrationParams,
+) -> Result<(sr25519::Pair, sr25519::Pair), Box<dyn std::error::Error>> {
+    let coldkey: sr25519::Pair = load_coldkey(
+        params.coldkey.as_deref(),
+        params.wallet_name.as_deref(),
+        params.wallet_path.as_deref(),
+    )?;
+    let hotkey: sr25519::Pair = load_hotkey(
+        params.hotkey.as_deref(),
+        params.wallet_name.as_deref(),
+        params.wallet_hotkey.as_deref(),
+        params.wallet_path.as_deref(),
+    )?;
+
+    Ok((coldkey, hotkey))
+}
+
+/// Estimates the transaction fee of a `burned_register` extrinsic
 ///
 /// # Arguments
 ///
 /// * `client` - A reference to the blockchain client
-/// * `netuid` - The network UID to check
+/// * `call_data` - The `burned_register` call arguments
+/// * `signer` - The coldkey signer paying for the extrinsic
 ///
 /// # Returns
 ///
-/// A `Result` containing the recycle cost as a `u64` if successful, or an `Err` if retrieval fails
-async fn get_recycle_cost(
+/// A `Result` containing the estimated fee in RAO, or an `Err` if the estimation fails
+async fn estimate_burned_register_fee(
     client: &OnlineClient<SubstrateConfig>,
-    netuid: u16,
+    call_data: &Composite<()>,
+    signer: &PairSigner<SubstrateConfig, sr25519::Pair>,
 ) -> Result<u64, Box<dyn std::error::Error>> {
-    let latest_block = client.blocks().at_latest().await?;
-    let burn_key = subxt::storage::dynamic(
-        "SubtensorModule",
-        "Burn",
-        vec![Value::primitive(scale_value::Primitive::U128(
-            netuid as u128,
-        ))],
-    );
-    let burn_cost: u64 = client
-        .storage()
-        .at(latest_block.hash())
-        .fetch(&burn_key)
+    let payload = DefaultPayload::new("SubtensorModule", "burned_register", call_data.clone());
+    estimate_fee(client, &payload, signer).await
+}
+
+/// Prints how long the registered hotkey stays immune and where it ranks for pruning afterwards
+///
+/// # Arguments
+///
+/// * `params` - A reference to `RegistrationParams` containing the chain endpoint
+/// * `registered` - The registration to report on
+///
+/// # Returns
+///
+/// A `Result` which is `Ok` if the report was printed, or an `Err` if the chain could not be read
+async fn print_immunity_report(
+    params: &RegistrationParams,
+    registered: &RegistrationOutcome,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Sampled from past blocks, so the report does not wait for new ones
+    const BLOCK_TIME_SAMPLE: u32 = 100;
+
+    let client = OnlineClient::<SubstrateConfig>::from_url(&params.chain_endpoint).await?;
+    let block_time = recent_block_time(&client, BLOCK_TIME_SAMPLE).await?;
+    let report = get_immunity_report(&client, registered.netuid, &registered.hotkey, block_time)
         .await?
-        .ok_or_else(|| "Burn value not found for the given netuid".to_string())?
-        .as_type::<u64>()?;
+        .ok_or("The hotkey no longer holds a UID")?;
+    println!("{}", report);
 
-    Ok(burn_cost)
+    Ok(())
 }
 
-// TODO: Return UID of the registered neuron
 /// Main function to run the registration script
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -215,12 +880,62 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Parse configuration parameters
     let params: RegistrationParams = parse_config()?;
 
-    // Attempt to register hotkey
-    if let Err(e) = register_hotkey(&params).await {
-        error!("Error during registration: {}", e);
-        return Err(e);
+    if params.budget_status {
+        let budget = load_budget(&params)?.ok_or("total_budget_tao is not set")?;
+        println!("{}", budget);
+        return Ok(());
     }
 
-    info!("Registration process completed successfully.");
-    Ok(())
+    if params.dry_run {
+        info!("Dry run: the registration extrinsic will not be broadcast.");
+        if let Err(e) = dry_run_hotkey(&params).await {
+            error!("Error during dry run: {}", e);
+            return Err(e);
+        }
+        return Ok(());
+    }
+
+    loop {
+        // Attempt to register hotkey
+        let outcome = match register_hotkey(&params).await {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                error!("Error during registration: {}", e);
+                return Err(e);
+            }
+        };
+
+        let Some(outcome) = outcome else {
+            info!("Registration process stopped without registering the hotkey.");
+            return Ok(());
+        };
+        info!("Registration process completed successfully.");
+        println!("{}", outcome);
+        let report = async {
+            if let Err(e) = print_immunity_report(&params, &outcome).await {
+                warn!("Failed to build the immunity report: {}", e);
+            }
+        };
+        if !params.watch && !params.reregister {
+            report.await;
+            return Ok(());
+        }
+
+        // Wait for the hotkey to be pruned, then register it again under the same limits. The
+        // watch starts first so no deregistration is missed while the report is built.
+        let (deregistration, ()) =
+            tokio::join!(watch_registration(&params.chain_endpoint, &outcome), report);
+        let deregistration = match deregistration {
+            Ok(deregistration) => deregistration,
+            Err(e) => {
+                error!("Error while watching the registration: {}", e);
+                return Err(e);
+            }
+        };
+        alert(&deregistration, params.alert_command.as_deref()).await;
+        if !params.reregister {
+            return Ok(());
+        }
+        info!("🔁 Registering the hotkey again.");
+    }
 }
@@ -0,0 +1,178 @@
+//! Storage queries about a hotkey's membership in a subnet.
+//!
+//! A registered hotkey owns a UID through two entries: `Uids(netuid, hotkey)` maps it to the UID
+//! and `Keys(netuid, uid)` maps the UID back to it. When the hotkey is pruned, the UID is handed
+//! to the new registration, which rewrites `Keys` and removes the hotkey's `Uids` entry.
+
+use crate::watch::StorageWatcher;
+use scale_value::Value;
+use std::fmt;
+use subxt::backend::rpc::RpcClient;
+use subxt::ext::codec::Decode;
+use subxt::storage::DynamicAddress;
+use subxt::utils::{AccountId32, H256};
+use subxt::{OnlineClient, SubstrateConfig};
+
+/// A watched hotkey that lost its UID
+#[derive(Debug, Clone)]
+pub struct Deregistration {
+    /// The subnet the hotkey was registered on
+    pub netuid: u16,
+    /// The UID the hotkey held
+    pub uid: u16,
+    /// The deregistered hotkey
+    pub hotkey: AccountId32,
+    /// Hash of the block the change was observed at
+    pub block_hash: H256,
+    /// The hotkey now holding the UID, `None` if the UID is empty
+    pub replaced_by: Option<AccountId32>,
+}
+
+impl fmt::Display for Deregistration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "hotkey {} lost UID {} on netuid {} at {:?}",
+            self.hotkey, self.uid, self.netuid, self.block_hash
+        )?;
+        match &self.replaced_by {
+            Some(replaced_by) => write!(f, ", now held by {}", replaced_by),
+            None => write!(f, ", now empty"),
+        }
+    }
+}
+
+/// Stream of the `Uids` and `Keys` entries binding a hotkey to its UID
+pub struct MembershipWatcher {
+    uids: StorageWatcher,
+    keys: StorageWatcher,
+    netuid: u16,
+    uid: u16,
+    hotkey: AccountId32,
+}
+
+impl MembershipWatcher {
+    /// Subscribes to the `Uids` entry of `hotkey` and the `Keys` entry of `uid` on `netuid`
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - A reference to the blockchain client, used to build the storage keys
+    /// * `rpc` - The RPC client to open the subscriptions on
+    /// * `netuid` - The subnet the hotkey is registered on
+    /// * `uid` - The UID the hotkey holds
+    /// * `hotkey` - The registered hotkey
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `MembershipWatcher` if both subscriptions were opened, or an
+    /// `Err` otherwise
+    pub async fn subscribe(
+        client: &OnlineClient<SubstrateConfig>,
+        rpc: &RpcClient,
+        netuid: u16,
+        uid: u16,
+        hotkey: &AccountId32,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let uids_key = client
+            .storage()
+            .address_bytes(&uids_address(netuid, hotkey))?;
+        let keys_key = client.storage().address_bytes(&keys_address(netuid, uid))?;
+
+        Ok(Self {
+            uids: StorageWatcher::subscribe(rpc, uids_key).await?,
+            keys: StorageWatcher::subscribe(rpc, keys_key).await?,
+            netuid,
+            uid,
+            hotkey: hotkey.clone(),
+        })
+    }
+
+    /// Waits until the hotkey no longer holds its UID
+    ///
+    /// The first notification of each subscription carries the current value, so a hotkey that
+    /// was deregistered before the watch started is reported right away.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Deregistration`, or an `Err` if a subscription ended or a value
+    /// could not be decoded
+    pub async fn wait_for_deregistration(
+        &mut self,
+    ) -> Result<Deregistration, Box<dyn std::error::Error>> {
+        let mut uid = Some(self.uid);
+        let mut owner = Some(self.hotkey.clone());
+        loop {
+            let block_hash = tokio::select! {
+                change = self.uids.next() => {
+                    let (block_hash, data) = change.ok_or("Uids subscription ended")??;
+                    uid = data.map(|data| u16::decode(&mut &data[..])).transpose()?;
+                    block_hash
+                }
+                change = self.keys.next() => {
+                    let (block_hash, data) = change.ok_or("Keys subscription ended")??;
+                    owner = data
+                        .map(|data| AccountId32::decode(&mut &data[..]))
+                        .transpose()?;
+                    block_hash
+                }
+            };
+
+            if uid != Some(self.uid) || owner.as_ref() != Some(&self.hotkey) {
+                return Ok(Deregistration {
+                    netuid: self.netuid,
+                    uid: self.uid,
+                    hotkey: self.hotkey.clone(),
+                    block_hash,
+                    replaced_by: owner.filter(|owner| owner != &self.hotkey),
+                });
+            }
+        }
+    }
+}
+
+/// Retrieves the UID of `hotkey` on `netuid` at the latest finalized block
+///
+/// # Arguments
+///
+/// * `client` - A reference to the blockchain client
+/// * `netuid` - The network UID to check
+/// * `hotkey` - The hotkey to look up
+///
+/// # Returns
+///
+/// A `Result` containing `Some(uid)` if the hotkey is registered on the subnet, `None` if it is not,
+/// or an `Err` if the query fails
+pub async fn get_uid(
+    client: &OnlineClient<SubstrateConfig>,
+    netuid: u16,
+    hotkey: &AccountId32,
+) -> Result<Option<u16>, Box<dyn std::error::Error>> {
+    let uid = client
+        .storage()
+        .at_latest()
+        .await?
+        .fetch(&uids_address(netuid, hotkey))
+        .await?
+        .map(|uid| uid.as_type::<u16>())
+        .transpose()?;
+
+    Ok(uid)
+}
+
+/// Builds the dynamic storage address of `SubtensorModule::Uids` for `hotkey` on `netuid`
+fn uids_address(netuid: u16, hotkey: &AccountId32) -> DynamicAddress<Vec<Value>> {
+    subxt::storage::dynamic(
+        "SubtensorModule",
+        "Uids",
+        vec![Value::u128(netuid as u128), Value::from_bytes(hotkey.0)],
+    )
+}
+
+/// Builds the dynamic storage address of `SubtensorModule::Keys` for `uid` on `netuid`
+fn keys_address(netuid: u16, uid: u16) -> DynamicAddress<Vec<Value>> {
+    subxt::storage::dynamic(
+        "SubtensorModule",
+        "Keys",
+        vec![Value::u128(netuid as u128), Value::u128(uid as u128)],
+    )
+}
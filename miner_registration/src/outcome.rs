@@ -0,0 +1,113 @@
+//! Decoding of the registration events and the summary returned to the caller.
+
+use std::fmt;
+use subxt::blocks::ExtrinsicEvents;
+use subxt::events::StaticEvent;
+use subxt::ext::scale_decode::DecodeAsType;
+use subxt::utils::{AccountId32, H256};
+use subxt::SubstrateConfig;
+
+/// `SubtensorModule::NeuronRegistered(netuid, uid, hotkey)`, emitted once the
+/// hotkey has been assigned a UID on the subnet.
+#[derive(Debug, DecodeAsType)]
+#[decode_as_type(crate_path = "subxt::ext::scale_decode")]
+pub struct NeuronRegistered(pub u16, pub u16, pub AccountId32);
+
+impl StaticEvent for NeuronRegistered {
+    const PALLET: &'static str = "SubtensorModule";
+    const EVENT: &'static str = "NeuronRegistered";
+}
+
+/// Summary of a successful registration
+#[derive(Debug, Clone)]
+pub struct RegistrationOutcome {
+    /// The subnet the hotkey is registered on
+    pub netuid: u16,
+    /// The UID assigned to the hotkey
+    pub uid: u16,
+    /// The registered hotkey
+    pub hotkey: AccountId32,
+    /// The extrinsic that registered the hotkey, `None` if it was registered before this run
+    /// or by an earlier attempt whose result was not observed
+    pub extrinsic: Option<RegistrationExtrinsic>,
+}
+
+/// Details of the `burned_register` extrinsic that registered the hotkey
+#[derive(Debug, Clone)]
+pub struct RegistrationExtrinsic {
+    /// Number of the block that included the registration
+    pub block_number: u32,
+    /// Hash of the block that included the registration
+    pub block_hash: H256,
+    /// Hash of the `burned_register` extrinsic
+    pub extrinsic_hash: H256,
+    /// The burn paid for the registration, in RAO
+    pub burn_paid_rao: u64,
+    /// The tip the extrinsic was signed with, in RAO
+    pub tip_rao: u64,
+}
+
+impl RegistrationOutcome {
+    /// Outcome for a hotkey found registered without an observed registration extrinsic
+    pub fn already_registered(netuid: u16, uid: u16, hotkey: AccountId32) -> Self {
+        Self {
+            netuid,
+            uid,
+            hotkey,
+            extrinsic: None,
+        }
+    }
+}
+
+impl fmt::Display for RegistrationOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Registration outcome:")?;
+        writeln!(f, "UID: {}", self.uid)?;
+        writeln!(f, "Netuid: {}", self.netuid)?;
+        write!(f, "Hotkey: {}", self.hotkey)?;
+        match &self.extrinsic {
+            Some(extrinsic) => {
+                writeln!(f)?;
+                writeln!(f, "Block number: {}", extrinsic.block_number)?;
+                writeln!(f, "Block hash: {:?}", extrinsic.block_hash)?;
+                writeln!(f, "Extrinsic hash: {:?}", extrinsic.extrinsic_hash)?;
+                writeln!(
+                    f,
+                    "Burn paid: {:.9} TAO",
+                    extrinsic.burn_paid_rao as f64 / 1e9
+                )?;
+                write!(f, "Tip paid: {:.9} TAO", extrinsic.tip_rao as f64 / 1e9)
+            }
+            None => write!(
+                f,
+                "\nAlready registered: no extrinsic submitted by this run"
+            ),
+        }
+    }
+}
+
+/// Finds the `NeuronRegistered` event for `hotkey` on `netuid` among the events of an extrinsic
+///
+/// # Arguments
+///
+/// * `events` - The events emitted by the registration extrinsic
+/// * `netuid` - The network UID the hotkey was registered on
+/// * `hotkey` - The registered hotkey
+///
+/// # Returns
+///
+/// A `Result` containing the decoded event, or an `Err` if no matching event was emitted
+pub fn find_neuron_registered(
+    events: &ExtrinsicEvents<SubstrateConfig>,
+    netuid: u16,
+    hotkey: &AccountId32,
+) -> Result<NeuronRegistered, Box<dyn std::error::Error>> {
+    for event in events.find::<NeuronRegistered>() {
+        let event = event?;
+        if event.0 == netuid && &event.2 == hotkey {
+            return Ok(event);
+        }
+    }
+
+    Err("NeuronRegistered event not found for the registered hotkey".into())
+}
@@ -0,0 +1,450 @@
+//! Proof-of-work registration through `SubtensorModule::register`.
+//!
+//! The seal is computed the same way as subtensor's `create_seal_hash`:
+//! `keccak256(sha256(nonce_le || keccak256(block_hash || hotkey)))`, and it meets the
+//! difficulty when `seal * difficulty` does not overflow a `U256`. The runtime only accepts
+//! work for a block that is less than three blocks old, so the solver is restarted on a fresh
+//! block once its work goes stale.
+
+use crate::outcome::{find_neuron_registered, RegistrationExtrinsic, RegistrationOutcome};
+use log::{error, info, warn};
+use scale_value::{Composite, Value};
+use shared::errors::Error;
+use shared::tx::dry_run;
+use sp_core::hashing::{keccak_256, sha2_256};
+use sp_core::U256;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use subxt::backend::rpc::RpcClient;
+use subxt::ext::sp_core::{sr25519, Pair};
+use subxt::tx::{DefaultPayload, PairSigner};
+use subxt::utils::{AccountId32, H256};
+use subxt::{OnlineClient, SubstrateConfig};
+
+/// Number of blocks after which the runtime rejects work with `InvalidWorkBlock`
+const WORK_VALIDITY_BLOCKS: u64 = 3;
+
+/// Work that satisfies the difficulty of a subnet at a given block
+#[derive(Debug, Clone)]
+pub struct PowSolution {
+    /// The block the work was computed against
+    pub block_number: u64,
+    /// The nonce that produced the seal
+    pub nonce: u64,
+    /// The seal hash submitted as `work`
+    pub seal: [u8; 32],
+}
+
+/// Registers `hotkey` on `netuid` with proof of work
+///
+/// The solver runs on `threads` CPU threads against the latest best block and is refreshed
+/// whenever its block is about to fall out of the validity window. The `register` extrinsic
+/// is signed by the hotkey, as the runtime requires.
+///
+/// # Arguments
+///
+/// * `client` - A reference to the blockchain client
+/// * `netuid` - The network UID to register on
+/// * `hotkey` - The hotkey to register
+/// * `coldkey` - The coldkey that will own the hotkey
+/// * `threads` - The number of solver threads
+///
+/// # Returns
+///
+/// A `Result` containing the `RegistrationOutcome` if registration is successful, or an `Err` containing the error message
+pub async fn register_pow(
+    client: &OnlineClient<SubstrateConfig>,
+    netuid: u16,
+    hotkey: &sr25519::Pair,
+    coldkey: &AccountId32,
+    threads: usize,
+) -> Result<RegistrationOutcome, Box<dyn std::error::Error>> {
+    if !get_pow_registration_allowed(client, netuid).await? {
+        return Err(format!("PoW registration is disabled on netuid {}", netuid).into());
+    }
+
+    let hotkey_account = AccountId32(hotkey.public().0);
+    let signer = PairSigner::new(hotkey.clone());
+    let mut blocks = client.blocks().subscribe_best().await?;
+
+    loop {
+        let block = blocks
+            .next()
+            .await
+            .ok_or(Error::Disconnected("Block subscription ended".to_string()))??;
+        let work_block_number = block.number() as u64;
+        let difficulty = get_difficulty(client, netuid, block.hash()).await?;
+        info!(
+            "⛏️ Solving PoW for block {} with difficulty {} on {} threads",
+            work_block_number, difficulty, threads
+        );
+
+        // Solve on blocking threads until a solution is found or the work goes stale
+        let cancel = Arc::new(AtomicBool::new(false));
+        let solve_start = Instant::now();
+        let mut solver = {
+            let cancel = Arc::clone(&cancel);
+            let block_hash = block.hash();
+            let hotkey_account = hotkey_account.clone();
+            tokio::task::spawn_blocking(move || {
+                solve(
+                    work_block_number,
+                    block_hash,
+                    &hotkey_account,
+                    difficulty,
+                    threads,
+                    &cancel,
+                )
+            })
+        };
+
+        let solution = loop {
+            tokio::select! {
+                solution = &mut solver => break solution?,
+                next = blocks.next() => {
+                    let next = next.ok_or(Error::Disconnected("Block subscription ended".to_string()))??;
+                    if next.number() as u64 + 1 >= work_block_number + WORK_VALIDITY_BLOCKS {
+                        info!("♻️ Work for block {} is going stale, refreshing", work_block_number);
+                        cancel.store(true, Ordering::Relaxed);
+                        break solver.await?;
+                    }
+                }
+            }
+        };
+
+        let Some(solution) = solution else {
+            continue;
+        };
+        info!(
+            "⏱️ Found PoW solution for block {} (nonce {}) in {:?}",
+            solution.block_number,
+            solution.nonce,
+            solve_start.elapsed()
+        );
+
+        let payload = register_payload(netuid, &solution, &hotkey_account, coldkey);
+
+        let progress = match client
+            .tx()
+            .sign_and_submit_then_watch(&payload, &signer, Default::default())
+            .await
+        {
+            Ok(progress) => progress,
+            Err(e) => {
+                error!("Failed to submit PoW registration: {:?}", e);
+                continue;
+            }
+        };
+
+        let finalized = async {
+            let in_block = progress.wait_for_finalized().await?;
+            let events = in_block.wait_for_success().await?;
+            Ok::<_, subxt::Error>((in_block.block_hash(), events))
+        };
+        match finalized.await {
+            Ok((block_hash, events)) => {
+                let registered = find_neuron_registered(&events, netuid, &hotkey_account)?;
+                let block_number = client.blocks().at(block_hash).await?.number();
+                info!(
+                    "🎯 PoW registration successful at block {} ({:?}) with UID {}",
+                    block_number, block_hash, registered.1
+                );
+
+                return Ok(RegistrationOutcome {
+                    netuid: registered.0,
+                    uid: registered.1,
+                    hotkey: registered.2,
+                    extrinsic: Some(RegistrationExtrinsic {
+                        block_number,
+                        block_hash,
+                        extrinsic_hash: events.extrinsic_hash(),
+                        burn_paid_rao: 0,
+                        tip_rao: 0,
+                    }),
+                });
+            }
+            Err(e) => warn!("PoW registration failed: {:?}", e),
+        }
+    }
+}
+
+/// Signs a `register` extrinsic and validates it against the chain without broadcasting it
+///
+/// Solving the work can take arbitrarily long, so the extrinsic carries placeholder work for
+/// the latest block: its nonce and fee are accurate, but dispatching it fails on the seal.
+///
+/// # Arguments
+///
+/// * `client` - A reference to the blockchain client
+/// * `rpc` - The RPC client used for `system_dryRun`
+/// * `netuid` - The network UID to register on
+/// * `hotkey` - The hotkey to register, which signs the extrinsic
+/// * `coldkey` - The coldkey that will own the hotkey
+///
+/// # Returns
+///
+/// A `Result` which is `Ok` if the extrinsic could be built and validated, or an `Err` otherwise
+pub async fn dry_run_pow(
+    client: &OnlineClient<SubstrateConfig>,
+    rpc: &RpcClient,
+    netuid: u16,
+    hotkey: &sr25519::Pair,
+    coldkey: &AccountId32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let hotkey_account = AccountId32(hotkey.public().0);
+    let allowed = get_pow_registration_allowed(client, netuid).await?;
+    let block = client.blocks().at_latest().await?;
+    let difficulty = get_difficulty(client, netuid, block.hash()).await?;
+    let placeholder = PowSolution {
+        block_number: block.number().into(),
+        nonce: 0,
+        seal: [0; 32],
+    };
+    let payload = register_payload(netuid, &placeholder, &hotkey_account, coldkey);
+    let report = dry_run(client, rpc, &payload, &PairSigner::new(hotkey.clone()), 0).await?;
+
+    println!("Dry run of register (PoW) on netuid {}:", netuid);
+    println!("Hotkey: {}", hotkey_account);
+    println!("Coldkey: {}", coldkey);
+    println!(
+        "PoW registration: {}",
+        if allowed { "allowed" } else { "disabled" }
+    );
+    println!("Difficulty: {}", difficulty);
+    println!(
+        "The work is a placeholder for block {}, so dispatch fails on the seal",
+        placeholder.block_number
+    );
+    println!("{}", report);
+
+    Ok(())
+}
+
+/// Builds the `register` call submitting `solution` for `hotkey`
+fn register_payload(
+    netuid: u16,
+    solution: &PowSolution,
+    hotkey: &AccountId32,
+    coldkey: &AccountId32,
+) -> DefaultPayload<Composite<()>> {
+    DefaultPayload::new(
+        "SubtensorModule",
+        "register",
+        Composite::named([
+            ("netuid", netuid.into()),
+            ("block_number", solution.block_number.into()),
+            ("nonce", solution.nonce.into()),
+            ("work", Value::from_bytes(solution.seal)),
+            ("hotkey", Value::from_bytes(hotkey.0)),
+            ("coldkey", Value::from_bytes(coldkey.0)),
+        ]),
+    )
+}
+
+/// Searches nonces on `threads` threads for a seal that meets `difficulty`
+///
+/// # Arguments
+///
+/// * `block_number` - The number of the block the work is computed against
+/// * `block_hash` - The hash of that block
+/// * `hotkey` - The hotkey being registered
+/// * `difficulty` - The subnet difficulty
+/// * `threads` - The number of threads to search on
+/// * `cancel` - Set to stop the search early
+///
+/// # Returns
+///
+/// `Some(PowSolution)` if a seal was found, or `None` if the search was cancelled
+pub fn solve(
+    block_number: u64,
+    block_hash: H256,
+    hotkey: &AccountId32,
+    difficulty: u64,
+    threads: usize,
+    cancel: &AtomicBool,
+) -> Option<PowSolution> {
+    let block_and_hotkey_hash = hash_block_and_hotkey(block_hash, hotkey);
+
+    let threads = threads.max(1) as u64;
+    let start_nonce = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default();
+    let found = AtomicBool::new(false);
+
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|offset| {
+                let found = &found;
+                scope.spawn(move || {
+                    let mut nonce = start_nonce.wrapping_add(offset);
+                    while !found.load(Ordering::Relaxed) && !cancel.load(Ordering::Relaxed) {
+                        let seal = create_seal_hash(nonce, &block_and_hotkey_hash);
+                        if seal_meets_difficulty(&seal, difficulty) {
+                            found.store(true, Ordering::Relaxed);
+                            return Some(PowSolution {
+                                block_number,
+                                nonce,
+                                seal,
+                            });
+                        }
+                        nonce = nonce.wrapping_add(threads);
+                    }
+                    None
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .filter_map(|handle| handle.join().ok().flatten())
+            .next()
+    })
+}
+
+/// Mirrors subtensor's `hash_block_and_hotkey`, `keccak256(block_hash || hotkey)`
+fn hash_block_and_hotkey(block_hash: H256, hotkey: &AccountId32) -> [u8; 32] {
+    let mut block_and_hotkey = [0u8; 64];
+    block_and_hotkey[..32].copy_from_slice(block_hash.as_bytes());
+    block_and_hotkey[32..].copy_from_slice(&hotkey.0);
+    keccak_256(&block_and_hotkey)
+}
+
+/// Computes the seal for `nonce` from the precomputed `keccak256(block_hash || hotkey)`
+fn create_seal_hash(nonce: u64, block_and_hotkey_hash: &[u8; 32]) -> [u8; 32] {
+    let mut full_bytes = [0u8; 40];
+    full_bytes[..8].copy_from_slice(&nonce.to_le_bytes());
+    full_bytes[8..].copy_from_slice(block_and_hotkey_hash);
+    keccak_256(&sha2_256(&full_bytes))
+}
+
+/// Mirrors subtensor's `hash_meets_difficulty`
+fn seal_meets_difficulty(seal: &[u8; 32], difficulty: u64) -> bool {
+    let (_, overflowed) = U256::from_big_endian(seal).overflowing_mul(U256::from(difficulty));
+    !overflowed
+}
+
+/// Retrieves the PoW difficulty of `netuid` at a specific block
+///
+/// # Arguments
+///
+/// * `client` - A reference to the blockchain client
+/// * `netuid` - The network UID to check
+/// * `block_hash` - The hash of the block to read the storage at
+///
+/// # Returns
+///
+/// A `Result` containing the difficulty, or an `Err` if retrieval fails
+async fn get_difficulty(
+    client: &OnlineClient<SubstrateConfig>,
+    netuid: u16,
+    block_hash: H256,
+) -> Result<u64, Box<dyn std::error::Error>> {
+    let difficulty_key = subxt::storage::dynamic(
+        "SubtensorModule",
+        "Difficulty",
+        vec![Value::u128(netuid as u128)],
+    );
+    let difficulty = client
+        .storage()
+        .at(block_hash)
+        .fetch_or_default(&difficulty_key)
+        .await?
+        .as_type::<u64>()?;
+
+    Ok(difficulty)
+}
+
+/// Checks whether PoW registration is enabled on `netuid`
+async fn get_pow_registration_allowed(
+    client: &OnlineClient<SubstrateConfig>,
+    netuid: u16,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let allowed_key = subxt::storage::dynamic(
+        "SubtensorModule",
+        "NetworkPowRegistrationAllowed",
+        vec![Value::u128(netuid as u128)],
+    );
+    let allowed = client
+        .storage()
+        .at_latest()
+        .await?
+        .fetch_or_default(&allowed_key)
+        .await?
+        .as_type::<bool>()?;
+
+    Ok(allowed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use subxt::ext::sp_core::bytes::from_hex;
+
+    const BLOCK_HASH: &str = "0xba7ea4eb0b16dee271dbef5911838c3f359fcf598c74da65a54b919b68b67279";
+    /// Alice's public key
+    const HOTKEY: &str = "0xd43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d";
+
+    fn bytes32(hex: &str) -> [u8; 32] {
+        from_hex(hex).unwrap().try_into().unwrap()
+    }
+
+    fn block_and_hotkey_hash() -> [u8; 32] {
+        hash_block_and_hotkey(H256(bytes32(BLOCK_HASH)), &AccountId32(bytes32(HOTKEY)))
+    }
+
+    /// Seals computed with a separate Keccak-256 and SHA-256 implementation, following
+    /// subtensor's `create_seal_hash`
+    #[test]
+    fn seal_hash_matches_the_reference() {
+        assert_eq!(
+            block_and_hotkey_hash(),
+            bytes32("0xd830452ffc0b5619f592ec84b34aa73766999ae4a8a65f30c620fc490a9ca781")
+        );
+        assert_eq!(
+            create_seal_hash(0, &block_and_hotkey_hash()),
+            bytes32("0xa4cf4c71b4a2a7b318ba62da789fd84dcdaa008416e0b5ede6cbe87d303c4791")
+        );
+        assert_eq!(
+            create_seal_hash(39_357, &block_and_hotkey_hash()),
+            bytes32("0x0001388f413b65418fd75f60afb2e1f272d5956a9cc7989f69ec535996fd43ba")
+        );
+    }
+
+    #[test]
+    fn seal_meets_difficulty_up_to_the_overflow() {
+        // 2^256 / seal is just above 53676
+        let seal = bytes32("0x0001388f413b65418fd75f60afb2e1f272d5956a9cc7989f69ec535996fd43ba");
+        assert!(seal_meets_difficulty(&seal, 53_676));
+        assert!(!seal_meets_difficulty(&seal, 53_677));
+
+        let seal = bytes32("0xa4cf4c71b4a2a7b318ba62da789fd84dcdaa008416e0b5ede6cbe87d303c4791");
+        assert!(seal_meets_difficulty(&seal, 1));
+        assert!(!seal_meets_difficulty(&seal, 2));
+
+        assert!(seal_meets_difficulty(&[0; 32], u64::MAX));
+        assert!(!seal_meets_difficulty(&[0xff; 32], 2));
+    }
+
+    #[test]
+    fn solve_finds_a_seal_that_meets_the_difficulty() {
+        let hotkey = AccountId32(bytes32(HOTKEY));
+        let cancel = AtomicBool::new(false);
+        let solution = solve(7, H256(bytes32(BLOCK_HASH)), &hotkey, 1_000, 2, &cancel).unwrap();
+
+        assert_eq!(solution.block_number, 7);
+        assert_eq!(
+            solution.seal,
+            create_seal_hash(solution.nonce, &block_and_hotkey_hash())
+        );
+        assert!(seal_meets_difficulty(&solution.seal, 1_000));
+    }
+
+    #[test]
+    fn cancelled_solve_returns_none() {
+        let hotkey = AccountId32(bytes32(HOTKEY));
+        let cancel = AtomicBool::new(true);
+        assert!(solve(7, H256(bytes32(BLOCK_HASH)), &hotkey, u64::MAX, 2, &cancel).is_none());
+    }
+}
@@ -0,0 +1,244 @@
+//! Ahead-of-time signing of the `burned_register` extrinsic.
+//!
+//! Signing inside `sign_and_submit_then_watch` fetches the account nonce and the latest block
+//! over RPC while the new block is already in. The presigner keeps a ready-to-submit extrinsic
+//! instead: on every best block, and whenever the wanted tip changes, it re-signs the call in the
+//! background with an explicit nonce and a short mortality anchored at that block. Anchoring at
+//! the best block rather than the finalized one keeps the finality lag out of the validity
+//! window. The registration loop then only has to hand the encoded bytes to the broadcaster.
+
+use log::{info, warn};
+use scale_value::Composite;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use subxt::config::substrate::{BlakeTwo256, SubstrateExtrinsicParamsBuilder, SubstrateHeader};
+use subxt::ext::sp_core::sr25519;
+use subxt::tx::{DefaultPayload, PairSigner};
+use subxt::utils::H256;
+use subxt::{OnlineClient, SubstrateConfig};
+use tokio::sync::{Mutex, Notify};
+use tokio::task::JoinHandle;
+
+/// Number of blocks a presigned extrinsic stays valid for, rounded by the runtime to a power of two
+pub const MORTALITY_BLOCKS: u64 = 8;
+
+/// A signed `burned_register` extrinsic waiting to be submitted
+#[derive(Debug, Clone)]
+pub struct PresignedExtrinsic {
+    /// The SCALE encoded signed extrinsic
+    pub encoded: Vec<u8>,
+    /// Hash of the extrinsic
+    pub hash: H256,
+    /// Account nonce the extrinsic was signed with
+    pub nonce: u64,
+    /// Tip the extrinsic was signed with, in RAO
+    pub tip_rao: u64,
+    /// The block the mortality is anchored at
+    pub block_number: u64,
+    /// When the extrinsic was signed
+    pub signed_at: Instant,
+    /// How long fetching the nonce and signing took
+    pub sign_duration: Duration,
+}
+
+/// A presigned extrinsic tagged with the generation it was signed in
+type Tagged = (u64, PresignedExtrinsic);
+
+/// Keeps a freshly signed `burned_register` extrinsic ready in the background
+pub struct Presigner {
+    current: Arc<Mutex<Option<Tagged>>>,
+    /// Bumped by `invalidate`, so a signing task that started before cannot store its extrinsic
+    generation: Arc<AtomicU64>,
+    tip_rao: Arc<Mutex<u64>>,
+    refresh: Arc<Notify>,
+    handle: JoinHandle<()>,
+}
+
+impl Presigner {
+    /// Starts re-signing `call_data` on every best block
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The blockchain client
+    /// * `signer` - The coldkey signer paying for the extrinsic
+    /// * `call_data` - The `burned_register` call arguments
+    /// * `tip_rao` - The tip to sign the first extrinsic with
+    ///
+    /// # Returns
+    ///
+    /// The running `Presigner`
+    pub fn spawn(
+        client: Arc<OnlineClient<SubstrateConfig>>,
+        signer: Arc<PairSigner<SubstrateConfig, sr25519::Pair>>,
+        call_data: Arc<Composite<()>>,
+        tip_rao: u64,
+    ) -> Self {
+        let current = Arc::new(Mutex::new(None));
+        let generation = Arc::new(AtomicU64::new(0));
+        let tip_rao = Arc::new(Mutex::new(tip_rao));
+        let refresh = Arc::new(Notify::new());
+
+        let handle = {
+            let state = PresignerState {
+                current: Arc::clone(&current),
+                generation: Arc::clone(&generation),
+                tip_rao: Arc::clone(&tip_rao),
+                refresh: Arc::clone(&refresh),
+            };
+            tokio::spawn(async move {
+                if let Err(e) = run_presigner(client, signer, call_data, state).await {
+                    warn!("Presigner stopped, signing on the critical path: {}", e);
+                }
+            })
+        };
+
+        Self {
+            current,
+            generation,
+            tip_rao,
+            refresh,
+            handle,
+        }
+    }
+
+    /// Changes the tip of the presigned extrinsic, re-signing it right away
+    pub async fn set_tip(&self, tip_rao: u64) {
+        let mut tip_guard = self.tip_rao.lock().await;
+        if *tip_guard != tip_rao {
+            *tip_guard = tip_rao;
+            self.refresh.notify_one();
+        }
+    }
+
+    /// Discards the presigned extrinsic, e.g. after an attempt consumed its nonce
+    pub async fn invalidate(&self) {
+        let mut current = self.current.lock().await;
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        *current = None;
+        self.refresh.notify_one();
+    }
+
+    /// Returns the presigned extrinsic if it carries `tip_rao`
+    ///
+    /// # Arguments
+    ///
+    /// * `tip_rao` - The tip the extrinsic must be signed with
+    ///
+    /// # Returns
+    ///
+    /// `Some(PresignedExtrinsic)` if a matching extrinsic is ready, `None` otherwise
+    pub async fn get(&self, tip_rao: u64) -> Option<PresignedExtrinsic> {
+        let generation = self.generation.load(Ordering::SeqCst);
+        self.current
+            .lock()
+            .await
+            .as_ref()
+            .filter(|(signed_in, presigned)| {
+                *signed_in == generation && presigned.tip_rao == tip_rao
+            })
+            .map(|(_, presigned)| presigned.clone())
+    }
+}
+
+impl Drop for Presigner {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// The state the presigning task shares with its `Presigner`
+struct PresignerState {
+    current: Arc<Mutex<Option<Tagged>>>,
+    generation: Arc<AtomicU64>,
+    tip_rao: Arc<Mutex<u64>>,
+    refresh: Arc<Notify>,
+}
+
+/// Re-signs the call on every best block and on every refresh request
+async fn run_presigner(
+    client: Arc<OnlineClient<SubstrateConfig>>,
+    signer: Arc<PairSigner<SubstrateConfig, sr25519::Pair>>,
+    call_data: Arc<Composite<()>>,
+    state: PresignerState,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut blocks = client.blocks().subscribe_best().await?;
+    let payload = DefaultPayload::new("SubtensorModule", "burned_register", (*call_data).clone());
+    let mut header = blocks
+        .next()
+        .await
+        .ok_or("Block subscription ended")??
+        .header()
+        .clone();
+
+    loop {
+        let generation = state.generation.load(Ordering::SeqCst);
+        let tip = *state.tip_rao.lock().await;
+        let presigned = sign_at(&client, &signer, &payload, tip, &header).await?;
+
+        {
+            let mut current = state.current.lock().await;
+            // Invalidated while signing: the nonce may already be used, sign again right away
+            if state.generation.load(Ordering::SeqCst) != generation {
+                continue;
+            }
+            info!(
+                "✍️ Presigned burned_register at block {} (nonce {}, tip {:.9} TAO) in {:?}",
+                presigned.block_number,
+                presigned.nonce,
+                tip as f64 / 1e9,
+                presigned.sign_duration
+            );
+            *current = Some((generation, presigned));
+        }
+
+        tokio::select! {
+            block = blocks.next() => {
+                header = block.ok_or("Block subscription ended")??.header().clone();
+            }
+            _ = state.refresh.notified() => {}
+        }
+    }
+}
+
+/// Signs `payload` with the current account nonce and a mortality anchored at `header`
+///
+/// # Arguments
+///
+/// * `client` - The blockchain client
+/// * `signer` - The coldkey signer paying for the extrinsic
+/// * `payload` - The call to sign
+/// * `tip_rao` - The tip to sign the extrinsic with
+/// * `header` - The block the mortality is anchored at
+///
+/// # Returns
+///
+/// A `Result` containing the `PresignedExtrinsic`, or an `Err` if the nonce cannot be fetched
+/// or the call cannot be signed
+pub async fn sign_at(
+    client: &OnlineClient<SubstrateConfig>,
+    signer: &PairSigner<SubstrateConfig, sr25519::Pair>,
+    payload: &DefaultPayload<Composite<()>>,
+    tip_rao: u64,
+    header: &SubstrateHeader<u32, BlakeTwo256>,
+) -> Result<PresignedExtrinsic, Box<dyn std::error::Error + Send + Sync>> {
+    let sign_start = Instant::now();
+    let nonce = client.tx().account_nonce(signer.account_id()).await?;
+    let params = SubstrateExtrinsicParamsBuilder::new()
+        .tip(tip_rao as u128)
+        .nonce(nonce)
+        .mortal(header, MORTALITY_BLOCKS)
+        .build();
+    let extrinsic = client.tx().create_signed_offline(payload, signer, params)?;
+    let sign_duration = sign_start.elapsed();
+
+    Ok(PresignedExtrinsic {
+        encoded: extrinsic.encoded().to_vec(),
+        hash: extrinsic.hash(),
+        nonce,
+        tip_rao,
+        block_number: header.number.into(),
+        signed_at: Instant::now(),
+        sign_duration,
+    })
+}
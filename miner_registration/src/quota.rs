@@ -0,0 +1,198 @@
+//! Registration quota checks for a subnet.
+//!
+//! Subtensor rejects a registration with `TooManyRegistrationsThisBlock` once
+//! `RegistrationsThisBlock` reaches `MaxRegistrationsPerBlock`, and with
+//! `TooManyRegistrationsThisInterval` once `RegistrationsThisInterval` reaches three times
+//! `TargetRegistrationsPerInterval`. Both counters are reset in `on_initialize`: the block
+//! counter on every block, the interval counter on the adjustment block.
+
+use crate::forecast::RegistrationTerms;
+use std::fmt;
+
+/// Multiple of `TargetRegistrationsPerInterval` the runtime allows per interval
+const INTERVAL_TARGET_MULTIPLIER: u32 = 3;
+
+/// The reason a registration would be rejected by the quota
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaExhausted {
+    /// `MaxRegistrationsPerBlock` is zero, so no block accepts a registration
+    BlockDisabled,
+    /// The interval allowance is used up until the next adjustment block
+    Interval {
+        registrations: u16,
+        max: u32,
+        resets_at: u64,
+    },
+}
+
+impl fmt::Display for QuotaExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuotaExhausted::BlockDisabled => {
+                write!(
+                    f,
+                    "registrations are disabled (MaxRegistrationsPerBlock is 0)"
+                )
+            }
+            QuotaExhausted::Interval {
+                registrations,
+                max,
+                resets_at,
+            } => write!(
+                f,
+                "interval quota exhausted ({}/{} registrations, resets at block {})",
+                registrations, max, resets_at
+            ),
+        }
+    }
+}
+
+/// The registration counters of a subnet at a block
+#[derive(Debug, Clone, Copy)]
+pub struct RegistrationQuota {
+    /// Registrations included in the block the counters were read at
+    pub registrations_this_block: u16,
+    /// Registrations allowed per block
+    pub max_registrations_per_block: u16,
+    /// Registrations counted since the last adjustment
+    pub registrations_this_interval: u16,
+    /// Three times `TargetRegistrationsPerInterval`
+    pub max_registrations_per_interval: u32,
+    /// The block whose `on_initialize` resets the interval counter
+    pub interval_resets_at: u64,
+    /// Whether the next block is the adjustment block
+    pub interval_resets_next_block: bool,
+}
+
+impl RegistrationQuota {
+    /// Builds the quota from the registration terms read at `current_block`
+    ///
+    /// # Arguments
+    ///
+    /// * `terms` - The registration terms of the subnet
+    /// * `current_block` - The number of the block the terms were read at
+    ///
+    /// # Returns
+    ///
+    /// The `RegistrationQuota` of the subnet at that block
+    pub fn from_terms(terms: &RegistrationTerms, current_block: u64) -> Self {
+        let interval_resets_at = terms.next_adjustment_block(current_block);
+
+        Self {
+            registrations_this_block: terms.registrations_this_block,
+            max_registrations_per_block: terms.max_registrations_per_block,
+            registrations_this_interval: terms.registrations_this_interval,
+            max_registrations_per_interval: terms.target_registrations_per_interval as u32
+                * INTERVAL_TARGET_MULTIPLIER,
+            interval_resets_at,
+            interval_resets_next_block: interval_resets_at == current_block + 1,
+        }
+    }
+
+    /// Checks whether an extrinsic submitted now would be rejected by the quota
+    ///
+    /// A full block does not block the next attempt: the extrinsic can only be included in a
+    /// later block, whose `on_initialize` resets `RegistrationsThisBlock`. Likewise, a used-up
+    /// interval is ignored when the next block starts a new one.
+    ///
+    /// # Returns
+    ///
+    /// `Some(QuotaExhausted)` with the reason if the attempt should be skipped, `None` otherwise
+    pub fn exhausted(&self) -> Option<QuotaExhausted> {
+        if self.max_registrations_per_block == 0 {
+            return Some(QuotaExhausted::BlockDisabled);
+        }
+
+        if !self.interval_resets_next_block
+            && self.registrations_this_interval as u32 >= self.max_registrations_per_interval
+        {
+            return Some(QuotaExhausted::Interval {
+                registrations: self.registrations_this_interval,
+                max: self.max_registrations_per_interval,
+                resets_at: self.interval_resets_at,
+            });
+        }
+
+        None
+    }
+}
+
+impl fmt::Display for RegistrationQuota {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "block {}/{}, interval {}/{}",
+            self.registrations_this_block,
+            self.max_registrations_per_block,
+            self.registrations_this_interval,
+            self.max_registrations_per_interval
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(
+        registrations_this_interval: u16,
+        max_registrations_per_block: u16,
+    ) -> RegistrationTerms {
+        RegistrationTerms {
+            burn: 1_000_000_000,
+            min_burn: 500_000,
+            max_burn: 100_000_000_000_000,
+            adjustment_alpha: 0,
+            adjustment_interval: 360,
+            last_adjustment_block: 1_000,
+            target_registrations_per_interval: 2,
+            registrations_this_interval,
+            pow_registrations_this_interval: 0,
+            burn_registrations_this_interval: registrations_this_interval,
+            registrations_this_block: 1,
+            max_registrations_per_block,
+        }
+    }
+
+    #[test]
+    fn open_quota_is_not_exhausted() {
+        let quota = RegistrationQuota::from_terms(&terms(5, 1), 1_100);
+        assert_eq!(quota.max_registrations_per_interval, 6);
+        assert_eq!(quota.exhausted(), None);
+    }
+
+    #[test]
+    fn full_block_is_not_exhausted() {
+        // The extrinsic lands in a later block, which resets the block counter
+        let quota = RegistrationQuota::from_terms(&terms(0, 1), 1_100);
+        assert_eq!(
+            quota.registrations_this_block,
+            quota.max_registrations_per_block
+        );
+        assert_eq!(quota.exhausted(), None);
+    }
+
+    #[test]
+    fn disabled_block_registrations_are_exhausted() {
+        let quota = RegistrationQuota::from_terms(&terms(0, 0), 1_100);
+        assert_eq!(quota.exhausted(), Some(QuotaExhausted::BlockDisabled));
+    }
+
+    #[test]
+    fn used_up_interval_is_exhausted_until_the_adjustment() {
+        let quota = RegistrationQuota::from_terms(&terms(6, 1), 1_100);
+        assert_eq!(
+            quota.exhausted(),
+            Some(QuotaExhausted::Interval {
+                registrations: 6,
+                max: 6,
+                resets_at: 1_360,
+            })
+        );
+
+        // The next block is the adjustment block, which resets the interval counter
+        let quota = RegistrationQuota::from_terms(&terms(6, 1), 1_359);
+        assert!(quota.interval_resets_next_block);
+        assert_eq!(quota.exhausted(), None);
+    }
+}
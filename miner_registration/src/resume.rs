@@ -0,0 +1,182 @@
+//! Resolution of the submissions a previous run left without an outcome.
+//!
+//! Registration extrinsics are signed with an explicit nonce and a short mortality, so a pending
+//! submission can only be included in the finalized blocks of its validity window. The window is
+//! scanned for the extrinsic hash; if it is not there, the submission is settled once its nonce
+//! is used by another extrinsic or the window has passed, waiting for new finalized blocks until
+//! then.
+
+use crate::journal::{Journal, OutcomeStatus, Submission};
+use crate::outcome::find_neuron_registered;
+use log::{info, warn};
+use shared::budget::{Budget, Spend};
+use shared::errors::Error;
+use shared::tx::find_extrinsic;
+use subxt::backend::legacy::LegacyRpcMethods;
+use subxt::backend::rpc::RpcClient;
+use subxt::blocks::ExtrinsicEvents;
+use subxt::{OnlineClient, SubstrateConfig};
+
+/// Where a pending submission ended up
+enum Resolution {
+    /// Included in a finalized block
+    Included {
+        block_number: u64,
+        events: ExtrinsicEvents<SubstrateConfig>,
+    },
+    /// The nonce was used by another extrinsic
+    Replaced,
+    /// The validity window passed without the extrinsic
+    Expired,
+}
+
+/// Looks up every pending submission of the journal on chain and records its outcome
+///
+/// Included submissions are also counted against the budget, if one is set.
+///
+/// # Arguments
+///
+/// * `client` - The blockchain client
+/// * `rpc` - The RPC client, used to look up block hashes by number
+/// * `journal` - The journal holding the pending submissions
+/// * `budget` - The total spend budget, if one is set
+///
+/// # Returns
+///
+/// A `Result` which is `Ok` once every pending submission has an outcome, or an `Err` if the
+/// chain could not be queried
+pub async fn resolve_pending(
+    client: &OnlineClient<SubstrateConfig>,
+    rpc: &RpcClient,
+    journal: &mut Journal,
+    mut budget: Option<&mut Budget>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let pending = journal.pending().to_vec();
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    let rpc_methods = LegacyRpcMethods::<SubstrateConfig>::new(rpc.clone());
+    let mut blocks = client.blocks().subscribe_finalized().await?;
+    for submission in pending {
+        info!(
+            "📓 Looking up extrinsic {:?} (nonce {}) from a previous run, valid in blocks {}..{}",
+            submission.extrinsic_hash,
+            submission.nonce,
+            submission.block_number,
+            submission.valid_until
+        );
+
+        let mut next_block = submission.block_number;
+        let resolution = loop {
+            let head = client.blocks().at_latest().await?;
+            let head_number: u64 = head.number().into();
+
+            // Scan the finalized part of the validity window that was not scanned yet
+            let last_block = head_number.min(submission.valid_until - 1);
+            let mut included = None;
+            while included.is_none() && next_block <= last_block {
+                included =
+                    find_extrinsic(client, &rpc_methods, next_block, submission.extrinsic_hash)
+                        .await?
+                        .map(|(_, events)| Resolution::Included {
+                            block_number: next_block,
+                            events,
+                        });
+                next_block += 1;
+            }
+            if let Some(included) = included {
+                break included;
+            }
+
+            if head_number + 1 >= submission.valid_until {
+                break Resolution::Expired;
+            }
+            if head.account_nonce(&submission.coldkey).await? > submission.nonce {
+                break Resolution::Replaced;
+            }
+
+            blocks
+                .next()
+                .await
+                .ok_or(Error::Disconnected("Block subscription ended".to_string()))??;
+        };
+
+        record_resolution(journal, budget.as_deref_mut(), &submission, resolution)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the outcome of a resolved submission to the journal and the budget
+fn record_resolution(
+    journal: &mut Journal,
+    budget: Option<&mut Budget>,
+    submission: &Submission,
+    resolution: Resolution,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match resolution {
+        Resolution::Included {
+            block_number,
+            events,
+        } => {
+            if let Some(budget) = budget {
+                budget.record(Spend::from_events(&events, &submission.coldkey)?)?;
+            }
+            match find_neuron_registered(&events, submission.netuid, &submission.hotkey) {
+                Ok(registered) => {
+                    info!(
+                        "📓 Extrinsic {:?} registered the hotkey with UID {} at block {}",
+                        submission.extrinsic_hash, registered.1, block_number
+                    );
+                    journal.outcome(
+                        submission.extrinsic_hash,
+                        OutcomeStatus::Registered,
+                        Some(block_number),
+                        Some(registered.1),
+                        None,
+                    )
+                }
+                Err(e) => {
+                    warn!(
+                        "📓 Extrinsic {:?} was included at block {} without registering the hotkey",
+                        submission.extrinsic_hash, block_number
+                    );
+                    journal.outcome(
+                        submission.extrinsic_hash,
+                        OutcomeStatus::Failed,
+                        Some(block_number),
+                        None,
+                        Some(e.to_string()),
+                    )
+                }
+            }
+        }
+        Resolution::Replaced => {
+            info!(
+                "📓 Extrinsic {:?} was not included, its nonce was used by another extrinsic",
+                submission.extrinsic_hash
+            );
+            journal.outcome(
+                submission.extrinsic_hash,
+                OutcomeStatus::Replaced,
+                None,
+                None,
+                None,
+            )
+        }
+        Resolution::Expired => {
+            info!(
+                "📓 Extrinsic {:?} was not included before block {}",
+                submission.extrinsic_hash, submission.valid_until
+            );
+            journal.outcome(
+                submission.extrinsic_hash,
+                OutcomeStatus::Expired,
+                None,
+                None,
+                None,
+            )
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! Push-based watching of a single storage key.
+//!
+//! The watcher subscribes to the key with `state_subscribeStorage`. The node sends the current
+//! value first and then a notification whenever the value changes.
+
+use subxt::backend::legacy::rpc_methods::{Bytes, StorageChangeSet};
+use subxt::backend::rpc::{rpc_params, RpcClient, RpcSubscription};
+use subxt::utils::H256;
+
+/// Stream of changes of a single storage key
+pub struct StorageWatcher {
+    subscription: RpcSubscription<StorageChangeSet<H256>>,
+    key: Vec<u8>,
+}
+
+impl StorageWatcher {
+    /// Subscribes to changes of the storage entry at `key`
+    ///
+    /// # Arguments
+    ///
+    /// * `rpc` - The RPC client to open the subscription on
+    /// * `key` - The full storage key to watch
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `StorageWatcher` if the subscription was opened, or an `Err` otherwise
+    pub async fn subscribe(rpc: &RpcClient, key: Vec<u8>) -> Result<Self, subxt::Error> {
+        let subscription = rpc
+            .subscribe(
+                "state_subscribeStorage",
+                rpc_params![vec![Bytes(key.clone())]],
+                "state_unsubscribeStorage",
+            )
+            .await?;
+
+        Ok(Self { subscription, key })
+    }
+
+    /// Waits for the next change of the watched key
+    ///
+    /// # Returns
+    ///
+    /// `None` if the subscription ended, otherwise the hash of the block the change was observed
+    /// at and the raw value (`None` if the entry was removed), or an `Err` from the subscription
+    pub async fn next(&mut self) -> Option<Result<(H256, Option<Vec<u8>>), subxt::Error>> {
+        loop {
+            let change_set = match self.subscription.next().await? {
+                Ok(change_set) => change_set,
+                Err(e) => return Some(Err(e)),
+            };
+
+            if let Some((_, data)) = change_set
+                .changes
+                .into_iter()
+                .find(|(key, _)| key.0 == self.key)
+            {
+                return Some(Ok((change_set.block, data.map(|data| data.0))));
+            }
+        }
+    }
+}
@@ -0,0 +1,337 @@
+//! Broadcasting one signed extrinsic to several RPC endpoints.
+//!
+//! Inclusion in the next block depends on how fast the extrinsic propagates to the block
+//! author, so a single slow or lagging node can lose a registration race. The broadcaster
+//! submits the same encoded bytes to every endpoint with `author_submitAndWatchExtrinsic` at
+//! once and follows whichever endpoint reports the extrinsic in a block first. All endpoints
+//! carry the same extrinsic hash, so the submissions dedupe to a single transaction.
+//!
+//! The primary client is reconnected by its owner. An extra endpoint that drops is reconnected in
+//! the background and left out of broadcasts until it is back.
+
+use crate::errors::Error;
+use crate::resilient::{is_connection_error, ResilientClient};
+use futures::stream::{FuturesUnordered, StreamExt};
+use log::{debug, info, warn};
+use std::sync::Mutex;
+use subxt::tx::{SubmittableExtrinsic, TxInBlock, TxProgress, TxStatus};
+use subxt::utils::H256;
+use subxt::{OnlineClient, SubstrateConfig};
+use tokio::task::JoinHandle;
+
+/// A client connected to one of the broadcast endpoints
+type EndpointClient = (String, OnlineClient<SubstrateConfig>);
+
+/// An extra broadcast endpoint, reconnected in the background when it drops
+struct ExtraEndpoint {
+    connection: ResilientClient,
+    /// The running reconnection, if any
+    reconnect: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl ExtraEndpoint {
+    /// Returns `true` while the endpoint is being reconnected
+    fn is_reconnecting(&self) -> bool {
+        self.reconnect
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .as_ref()
+            .is_some_and(|handle| !handle.is_finished())
+    }
+
+    /// Starts reconnecting in the background, unless a reconnection is already running
+    fn start_reconnect(&self) {
+        let mut reconnect = self
+            .reconnect
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if reconnect
+            .as_ref()
+            .is_some_and(|handle| !handle.is_finished())
+        {
+            return;
+        }
+        let connection = self.connection.clone();
+        *reconnect = Some(tokio::spawn(async move { connection.reconnect().await }));
+    }
+}
+
+/// Submits signed extrinsics to a set of RPC endpoints at the same time
+pub struct Broadcaster {
+    primary: EndpointClient,
+    extras: Vec<ExtraEndpoint>,
+}
+
+/// The first report of a broadcast extrinsic being included in a block
+pub struct Broadcast {
+    /// The endpoint that reported the inclusion first
+    pub endpoint: String,
+    /// Hash of the extrinsic shared by all submissions
+    pub extrinsic_hash: H256,
+    /// The block the extrinsic was included in
+    pub in_block: TxInBlock<SubstrateConfig, OnlineClient<SubstrateConfig>>,
+    /// The rest of the status stream of `endpoint`, `None` if the block was already finalized
+    progress: Option<TxProgress<SubstrateConfig, OnlineClient<SubstrateConfig>>>,
+}
+
+impl Broadcast {
+    /// Follows the winning endpoint until the extrinsic is in a finalized block
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the finalized `TxInBlock`, or an `Err` if the extrinsic was dropped
+    /// or the status stream ended
+    pub async fn wait_for_finalized(
+        self,
+    ) -> Result<TxInBlock<SubstrateConfig, OnlineClient<SubstrateConfig>>, subxt::Error> {
+        match self.progress {
+            Some(progress) => progress.wait_for_finalized().await,
+            None => Ok(self.in_block),
+        }
+    }
+}
+
+impl Broadcaster {
+    /// Creates a broadcaster from an already connected primary client and extra endpoints
+    ///
+    /// Extra endpoints that cannot be reached are skipped with a warning, so one dead node does
+    /// not stop the registration. Extra endpoints that drop later are reconnected.
+    ///
+    /// # Arguments
+    ///
+    /// * `primary_endpoint` - The URL of the primary client, used in logs
+    /// * `primary` - The primary blockchain client
+    /// * `extra_endpoints` - URLs of the additional endpoints to broadcast to
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Broadcaster`, or an `Err` if it could not be created
+    pub async fn connect(
+        primary_endpoint: &str,
+        primary: OnlineClient<SubstrateConfig>,
+        extra_endpoints: &[String],
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut extras: Vec<ExtraEndpoint> = Vec::new();
+        for endpoint in extra_endpoints {
+            if endpoint == primary_endpoint
+                || extras
+                    .iter()
+                    .any(|extra| extra.connection.endpoint() == endpoint)
+            {
+                continue;
+            }
+            match ResilientClient::connect(endpoint).await {
+                Ok(connection) => extras.push(ExtraEndpoint {
+                    connection,
+                    reconnect: Mutex::new(None),
+                }),
+                Err(e) => warn!("Skipping broadcast endpoint {}: {}", endpoint, e),
+            }
+        }
+        info!("📡 Broadcasting to {} endpoint(s)", extras.len() + 1);
+
+        Ok(Self {
+            primary: (primary_endpoint.to_string(), primary),
+            extras,
+        })
+    }
+
+    /// Submits `encoded` to every endpoint and waits for the first to report it in a block
+    ///
+    /// Extra endpoints that are being reconnected are skipped, and those whose connection drops
+    /// during the submission start reconnecting.
+    ///
+    /// # Arguments
+    ///
+    /// * `encoded` - The SCALE encoded signed extrinsic
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the first `Broadcast` to reach a block, or an `Err` if no endpoint
+    /// got the extrinsic into a block: `Error::Disconnected` if every endpoint failed on its
+    /// connection, otherwise `Error::Subxt` with the error of the primary endpoint
+    pub async fn submit_and_watch(
+        &self,
+        encoded: &[u8],
+    ) -> Result<Broadcast, Box<dyn std::error::Error>> {
+        let extras: Vec<EndpointClient> = self
+            .extras
+            .iter()
+            .filter(|extra| !extra.is_reconnecting())
+            .map(|extra| {
+                let connection = &extra.connection;
+                (connection.endpoint().to_string(), connection.client())
+            })
+            .collect();
+        let mut pending: FuturesUnordered<_> = std::iter::once(&self.primary)
+            .chain(&extras)
+            .map(|(endpoint, client)| watch_until_in_block(endpoint, client, encoded.to_vec()))
+            .collect();
+
+        let mut errors = Vec::new();
+        while let Some(result) = pending.next().await {
+            match result {
+                Ok(broadcast) => return Ok(broadcast),
+                Err((endpoint, e)) => {
+                    debug!("Broadcast to {} did not reach a block: {}", endpoint, e);
+                    if is_connection_error(&e) {
+                        self.reconnect(&endpoint);
+                    }
+                    errors.push((endpoint, e));
+                }
+            }
+        }
+
+        let summary = errors
+            .iter()
+            .map(|(endpoint, e)| format!("{}: {}", endpoint, e))
+            .collect::<Vec<_>>()
+            .join("; ");
+        if errors.iter().all(|(_, e)| is_connection_error(e)) {
+            return Err(Error::Disconnected(format!(
+                "No endpoint included the extrinsic: {}",
+                summary
+            ))
+            .into());
+        }
+        warn!("No endpoint included the extrinsic: {}", summary);
+
+        // The primary's own error keeps its type, so the caller can classify it
+        match errors
+            .into_iter()
+            .find(|(endpoint, _)| *endpoint == self.primary.0)
+        {
+            Some((_, e)) => Err(Error::Subxt(e).into()),
+            None => Err(format!("No endpoint included the extrinsic: {}", summary).into()),
+        }
+    }
+
+    /// Starts reconnecting the extra endpoint `endpoint`, the primary is left to its owner
+    fn reconnect(&self, endpoint: &str) {
+        if let Some(extra) = self
+            .extras
+            .iter()
+            .find(|extra| extra.connection.endpoint() == endpoint)
+        {
+            warn!("🔌 Lost the connection to broadcast endpoint {}", endpoint);
+            extra.start_reconnect();
+        }
+    }
+}
+
+impl Drop for Broadcaster {
+    fn drop(&mut self) {
+        for extra in &self.extras {
+            if let Some(handle) = extra
+                .reconnect
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .take()
+            {
+                handle.abort();
+            }
+        }
+    }
+}
+
+/// Submits `encoded` to a single endpoint and follows it until it is in a block
+async fn watch_until_in_block(
+    endpoint: &str,
+    client: &OnlineClient<SubstrateConfig>,
+    encoded: Vec<u8>,
+) -> Result<Broadcast, (String, subxt::Error)> {
+    let fail = |e: subxt::Error| (endpoint.to_string(), e);
+    let extrinsic = SubmittableExtrinsic::from_bytes(client.clone(), encoded);
+    let extrinsic_hash = extrinsic.hash();
+    let mut progress = extrinsic.submit_and_watch().await.map_err(fail)?;
+
+    while let Some(status) = progress.next().await {
+        let (in_block, progress) = match status.map_err(fail)? {
+            TxStatus::InBestBlock(in_block) => (in_block, Some(progress)),
+            TxStatus::InFinalizedBlock(in_block) => (in_block, None),
+            TxStatus::Error { message }
+            | TxStatus::Invalid { message }
+            | TxStatus::Dropped { message } => {
+                return Err(fail(subxt::Error::Other(message)));
+            }
+            _ => continue,
+        };
+
+        return Ok(Broadcast {
+            endpoint: endpoint.to_string(),
+            extrinsic_hash,
+            in_block,
+            progress,
+        });
+    }
+
+    Err(fail(subxt::Error::Other(
+        "Transaction status stream ended".to_string(),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use subxt::backend::rpc::{RawRpcFuture, RawRpcSubscription, RawValue, RpcClient, RpcClientT};
+    use subxt::client::RuntimeVersion;
+    use subxt::error::RpcError;
+    use subxt::ext::codec::Decode;
+    use subxt::Metadata;
+
+    /// An RPC client whose connection is gone
+    struct DroppedRpc;
+
+    impl RpcClientT for DroppedRpc {
+        fn request_raw<'a>(
+            &'a self,
+            _method: &'a str,
+            _params: Option<Box<RawValue>>,
+        ) -> RawRpcFuture<'a, Box<RawValue>> {
+            Box::pin(async { Err(RpcError::SubscriptionDropped) })
+        }
+
+        fn subscribe_raw<'a>(
+            &'a self,
+            _sub: &'a str,
+            _params: Option<Box<RawValue>>,
+            _unsub: &'a str,
+        ) -> RawRpcFuture<'a, RawRpcSubscription> {
+            Box::pin(async { Err(RpcError::SubscriptionDropped) })
+        }
+    }
+
+    fn dropped_client() -> (RpcClient, OnlineClient<SubstrateConfig>) {
+        let metadata = Metadata::decode(&mut &include_bytes!("../../metadata.scale")[..]).unwrap();
+        let rpc = RpcClient::new(DroppedRpc);
+        let runtime_version = RuntimeVersion {
+            spec_version: 1,
+            transaction_version: 1,
+        };
+        let client = OnlineClient::from_rpc_client_with(
+            H256::zero(),
+            runtime_version,
+            metadata,
+            rpc.clone(),
+        )
+        .unwrap();
+        (rpc, client)
+    }
+
+    #[tokio::test]
+    async fn dropped_endpoints_are_a_connection_error() {
+        let (_, primary) = dropped_client();
+        let (rpc, client) = dropped_client();
+        let broadcaster = Broadcaster {
+            primary: ("ws://primary".to_string(), primary),
+            extras: vec![ExtraEndpoint {
+                connection: ResilientClient::from_clients("ws://extra", rpc, client),
+                reconnect: Mutex::new(None),
+            }],
+        };
+
+        let error = broadcaster.submit_and_watch(&[0]).await.err().unwrap();
+        assert!(is_connection_error(error.as_ref()), "{}", error);
+        assert!(broadcaster.extras[0].is_reconnecting());
+    }
+}
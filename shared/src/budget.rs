@@ -0,0 +1,348 @@
+//! A spend budget shared by every registration attempt, persisted across runs.
+//!
+//! `max_cost_tao` only bounds a single attempt. The budget adds up what each included extrinsic
+//! actually cost the coldkey, read back from its events: `TransactionPayment::TransactionFeePaid`
+//! gives the fee and the tip, and the coldkey's `Balances::Withdraw` and `Balances::Deposit`
+//! events give the net amount that left the account, the rest of which is the burn. The totals
+//! are saved to a JSON file after every attempt, so restarts and runs for other hotkeys draw from
+//! the same budget.
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use subxt::blocks::ExtrinsicEvents;
+use subxt::events::{EventDetails, StaticEvent};
+use subxt::ext::scale_decode::DecodeAsType;
+use subxt::utils::AccountId32;
+use subxt::SubstrateConfig;
+
+/// `TransactionPayment::TransactionFeePaid`, `actual_fee` includes the tip
+#[derive(Debug, DecodeAsType)]
+#[decode_as_type(crate_path = "subxt::ext::scale_decode")]
+pub struct TransactionFeePaid {
+    pub who: AccountId32,
+    pub actual_fee: u64,
+    pub tip: u64,
+}
+
+impl StaticEvent for TransactionFeePaid {
+    const PALLET: &'static str = "TransactionPayment";
+    const EVENT: &'static str = "TransactionFeePaid";
+}
+
+/// `Balances::Withdraw`, emitted for the fee and for the burn
+#[derive(Debug, DecodeAsType)]
+#[decode_as_type(crate_path = "subxt::ext::scale_decode")]
+pub struct Withdraw {
+    pub who: AccountId32,
+    pub amount: u64,
+}
+
+impl StaticEvent for Withdraw {
+    const PALLET: &'static str = "Balances";
+    const EVENT: &'static str = "Withdraw";
+}
+
+/// `Balances::Deposit`, emitted for the refund of an overestimated fee
+#[derive(Debug, DecodeAsType)]
+#[decode_as_type(crate_path = "subxt::ext::scale_decode")]
+pub struct Deposit {
+    pub who: AccountId32,
+    pub amount: u64,
+}
+
+impl StaticEvent for Deposit {
+    const PALLET: &'static str = "Balances";
+    const EVENT: &'static str = "Deposit";
+}
+
+/// What an extrinsic, or a series of them, cost the payer
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Spend {
+    /// Burned or locked by the call, in RAO
+    pub burn_rao: u64,
+    /// Transaction fees without the tip, in RAO
+    pub fee_rao: u64,
+    /// Tips, in RAO
+    pub tip_rao: u64,
+}
+
+impl Spend {
+    /// Reads what an included extrinsic cost `payer` from its events
+    ///
+    /// A failed dispatch still pays the fee and the tip, but its burn is rolled back together
+    /// with the burn's events.
+    ///
+    /// # Arguments
+    ///
+    /// * `events` - The events of the included extrinsic
+    /// * `payer` - The account paying for the extrinsic
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Spend`, or an `Err` if an event cannot be decoded
+    pub fn from_events(
+        events: &ExtrinsicEvents<SubstrateConfig>,
+        payer: &AccountId32,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::from_event_details(events.iter(), payer)
+    }
+
+    /// Adds up what `payer` paid across `events`, see [`Spend::from_events`]
+    fn from_event_details(
+        events: impl Iterator<Item = Result<EventDetails<SubstrateConfig>, subxt::Error>>,
+        payer: &AccountId32,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut actual_fee = 0u64;
+        let mut tip_rao = 0u64;
+        let mut withdrawn = 0u64;
+        let mut deposited = 0u64;
+        for event in events {
+            let event = event?;
+            if let Some(event) = event.as_event::<TransactionFeePaid>()? {
+                if &event.who == payer {
+                    actual_fee = actual_fee.saturating_add(event.actual_fee);
+                    tip_rao = tip_rao.saturating_add(event.tip);
+                }
+            } else if let Some(event) = event.as_event::<Withdraw>()? {
+                if &event.who == payer {
+                    withdrawn = withdrawn.saturating_add(event.amount);
+                }
+            } else if let Some(event) = event.as_event::<Deposit>()? {
+                if &event.who == payer {
+                    deposited = deposited.saturating_add(event.amount);
+                }
+            }
+        }
+
+        Ok(Self {
+            burn_rao: withdrawn
+                .saturating_sub(deposited)
+                .saturating_sub(actual_fee),
+            fee_rao: actual_fee.saturating_sub(tip_rao),
+            tip_rao,
+        })
+    }
+
+    /// The burn, fees and tips together, in RAO
+    pub fn total_rao(&self) -> u64 {
+        self.burn_rao
+            .saturating_add(self.fee_rao)
+            .saturating_add(self.tip_rao)
+    }
+}
+
+/// The spend totals saved to the budget file
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+struct Ledger {
+    /// Everything spent so far
+    spent: Spend,
+    /// Number of extrinsics that were included and paid for
+    attempts: u64,
+}
+
+/// A total spend limit backed by a file
+#[derive(Debug)]
+pub struct Budget {
+    path: PathBuf,
+    limit_rao: u64,
+    ledger: Ledger,
+}
+
+impl Budget {
+    /// Loads the spend recorded in `path`, starting from zero if the file does not exist
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The budget file
+    /// * `limit_rao` - The total amount that may be spent, in RAO
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Budget`, or an `Err` if the file exists but cannot be read
+    pub fn load(
+        path: impl AsRef<Path>,
+        limit_rao: u64,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = path.as_ref().to_path_buf();
+        let ledger = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| format!("Invalid budget file {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ledger::default(),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e).into()),
+        };
+
+        Ok(Self {
+            path,
+            limit_rao,
+            ledger,
+        })
+    }
+
+    /// What is left of the limit, in RAO
+    pub fn remaining_rao(&self) -> u64 {
+        self.limit_rao.saturating_sub(self.ledger.spent.total_rao())
+    }
+
+    /// Adds the cost of an included extrinsic and saves the totals
+    ///
+    /// The file is replaced through a temporary file, so a crash never leaves it half written.
+    ///
+    /// # Arguments
+    ///
+    /// * `spend` - What the extrinsic cost
+    ///
+    /// # Returns
+    ///
+    /// A `Result` which is `Ok` if the totals were saved, or an `Err` otherwise
+    pub fn record(&mut self, spend: Spend) -> Result<(), Box<dyn std::error::Error>> {
+        let spent = &mut self.ledger.spent;
+        spent.burn_rao = spent.burn_rao.saturating_add(spend.burn_rao);
+        spent.fee_rao = spent.fee_rao.saturating_add(spend.fee_rao);
+        spent.tip_rao = spent.tip_rao.saturating_add(spend.tip_rao);
+        self.ledger.attempts += 1;
+
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(&self.ledger)?)?;
+        fs::rename(&tmp_path, &self.path)?;
+
+        info!(
+            "💼 Spent {:.9} TAO ({:.9} TAO burn + {:.9} TAO fee + {:.9} TAO tip). {}",
+            spend.total_rao() as f64 / 1e9,
+            spend.burn_rao as f64 / 1e9,
+            spend.fee_rao as f64 / 1e9,
+            spend.tip_rao as f64 / 1e9,
+            self
+        );
+        Ok(())
+    }
+}
+
+impl fmt::Display for Budget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let spent = self.ledger.spent;
+        write!(
+            f,
+            "Budget: {:.9} of {:.9} TAO spent over {} attempt(s) ({:.9} TAO burn, {:.9} TAO fees, {:.9} TAO tips), {:.9} TAO remaining",
+            spent.total_rao() as f64 / 1e9,
+            self.limit_rao as f64 / 1e9,
+            self.ledger.attempts,
+            spent.burn_rao as f64 / 1e9,
+            spent.fee_rao as f64 / 1e9,
+            spent.tip_rao as f64 / 1e9,
+            self.remaining_rao() as f64 / 1e9
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use subxt::events::Events;
+    use subxt::ext::codec::{Compact, Decode, Encode};
+    use subxt::Metadata;
+
+    const PAYER: AccountId32 = AccountId32([1; 32]);
+    const OTHER: AccountId32 = AccountId32([2; 32]);
+
+    fn metadata() -> Metadata {
+        Metadata::decode(&mut &include_bytes!("../../metadata.scale")[..]).unwrap()
+    }
+
+    /// Encodes an `EventRecord` applied by extrinsic 1
+    fn record(metadata: &Metadata, pallet: &str, event: &str, fields: impl Encode) -> Vec<u8> {
+        let pallet = metadata.pallet_by_name(pallet).unwrap();
+        let variant = pallet
+            .event_variants()
+            .unwrap()
+            .iter()
+            .find(|variant| variant.name == event)
+            .unwrap();
+        let mut bytes = vec![0u8];
+        bytes.extend(1u32.encode());
+        bytes.extend([pallet.index(), variant.index]);
+        bytes.extend(fields.encode());
+        bytes.extend(Vec::<[u8; 32]>::new().encode());
+        bytes
+    }
+
+    fn spend(records: Vec<Vec<u8>>) -> Spend {
+        let metadata = metadata();
+        let mut bytes = Compact(records.len() as u32).encode();
+        records.into_iter().for_each(|record| bytes.extend(record));
+        let events = Events::<SubstrateConfig>::decode_from(bytes, metadata);
+        Spend::from_event_details(events.iter().map(|event| event.map_err(Into::into)), &PAYER)
+            .unwrap()
+    }
+
+    #[test]
+    fn burn_is_the_net_withdrawal_beyond_the_fee() {
+        let metadata = metadata();
+        let spend = spend(vec![
+            // Fee estimate withdrawn up front, overestimate refunded
+            record(&metadata, "Balances", "Withdraw", (PAYER.0, 150_000u64)),
+            record(
+                &metadata,
+                "Balances",
+                "Withdraw",
+                (PAYER.0, 1_000_000_000u64),
+            ),
+            record(&metadata, "Balances", "Deposit", (PAYER.0, 30_000u64)),
+            record(
+                &metadata,
+                "TransactionPayment",
+                "TransactionFeePaid",
+                (PAYER.0, 120_000u64, 20_000u64),
+            ),
+        ]);
+        assert_eq!(
+            spend,
+            Spend {
+                burn_rao: 1_000_000_000,
+                fee_rao: 100_000,
+                tip_rao: 20_000,
+            }
+        );
+        assert_eq!(spend.total_rao(), 1_000_120_000);
+    }
+
+    #[test]
+    fn failed_dispatch_only_pays_the_fee() {
+        let metadata = metadata();
+        let spend = spend(vec![
+            record(&metadata, "Balances", "Withdraw", (PAYER.0, 120_000u64)),
+            record(
+                &metadata,
+                "TransactionPayment",
+                "TransactionFeePaid",
+                (PAYER.0, 120_000u64, 0u64),
+            ),
+        ]);
+        assert_eq!(
+            spend,
+            Spend {
+                burn_rao: 0,
+                fee_rao: 120_000,
+                tip_rao: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn other_accounts_are_ignored() {
+        let metadata = metadata();
+        let spend = spend(vec![
+            record(&metadata, "Balances", "Withdraw", (OTHER.0, 5_000_000u64)),
+            record(&metadata, "Balances", "Deposit", (OTHER.0, 1_000u64)),
+            record(
+                &metadata,
+                "TransactionPayment",
+                "TransactionFeePaid",
+                (OTHER.0, 120_000u64, 10u64),
+            ),
+        ]);
+        assert_eq!(spend, Spend::default());
+    }
+}
@@ -1,10 +1,16 @@
+use crate::resilient::Backoff;
+use clap::{Args, ValueEnum};
 use std::fmt;
+use std::time::Duration;
 
 #[derive(Debug)]
 pub enum Error {
     BlockHeaderNotFound,
     ExceededMaxWaitTime,
-    SubxtError(subxt::Error),
+    Subxt(subxt::Error),
+    Disconnected(String),
+    Dispatch(DispatchErrorKind, String),
+    Keyfile(String),
     Other(String),
 }
 
@@ -15,7 +21,10 @@ impl fmt::Display for Error {
             Error::ExceededMaxWaitTime => {
                 write!(f, "Exceeded maximum wait time for block sampling")
             }
-            Error::SubxtError(e) => write!(f, "Subxt error: {}", e),
+            Error::Subxt(e) => write!(f, "Subxt error: {}", e),
+            Error::Disconnected(s) => write!(f, "Disconnected: {}", s),
+            Error::Dispatch(kind, s) => write!(f, "Extrinsic rejected ({}): {}", kind, s),
+            Error::Keyfile(s) => write!(f, "Keyfile error: {}", s),
             Error::Other(s) => write!(f, "Other error: {}", s),
         }
     }
@@ -25,6 +34,264 @@ impl std::error::Error for Error {}
 
 impl From<subxt::Error> for Error {
     fn from(error: subxt::Error) -> Self {
-        Error::SubxtError(error)
+        Error::Subxt(error)
+    }
+}
+
+/// Why the runtime or the transaction pool rejected a registration extrinsic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DispatchErrorKind {
+    /// `SubtensorModule::HotKeyAlreadyRegisteredInSubNet`
+    HotKeyAlreadyRegisteredInSubNet,
+    /// `SubtensorModule::NotEnoughBalanceToStake`, the coldkey cannot pay the burn or lock
+    NotEnoughBalanceToStake,
+    /// `SubtensorModule::TooManyRegistrationsThisBlock` or `TooManyRegistrationsThisInterval`
+    TooManyRegistrationsThisBlock,
+    /// `SubtensorModule::SubNetworkDoesNotExist`
+    SubNetworkDoesNotExist,
+    /// `SubtensorModule::SubNetRegistrationDisabled`
+    RegistrationDisabled,
+    /// The pool rejected the extrinsic because one with the same nonce pays more
+    PriorityTooLow,
+    /// The nonce was already used, or another extrinsic with the same nonce got in first
+    Stale,
+    /// The pool rejected the extrinsic as invalid
+    Invalid,
+    /// Any other runtime error or rejection
+    Other,
+}
+
+impl DispatchErrorKind {
+    /// Classifies the error of a failed submission or dispatch
+    ///
+    /// Runtime errors are decoded from the dispatch `ModuleError` through the metadata. Pool
+    /// rejections only reach the client as RPC error or transaction status messages, so they
+    /// are recognized by their message.
+    ///
+    /// # Arguments
+    ///
+    /// * `error` - The error returned by the submission or by `wait_for_success`
+    ///
+    /// # Returns
+    ///
+    /// The `DispatchErrorKind` of the error, `Other` if it is not recognized
+    pub fn classify(error: &(dyn std::error::Error + 'static)) -> Self {
+        let subxt_error = match error.downcast_ref::<Error>() {
+            Some(Error::Dispatch(kind, _)) => return *kind,
+            Some(Error::Subxt(e)) => Some(e),
+            _ => error.downcast_ref::<subxt::Error>(),
+        };
+
+        if let Some(subxt::Error::Runtime(subxt::error::DispatchError::Module(module_error))) =
+            subxt_error
+        {
+            return match module_error.details() {
+                Ok(details) => Self::from_module_error(&details.variant.name),
+                Err(_) => Self::Other,
+            };
+        }
+
+        Self::from_pool_message(&error.to_string())
+    }
+
+    /// Maps a `SubtensorModule` error name to its kind
+    fn from_module_error(name: &str) -> Self {
+        match name {
+            "HotKeyAlreadyRegisteredInSubNet" => Self::HotKeyAlreadyRegisteredInSubNet,
+            "NotEnoughBalanceToStake" => Self::NotEnoughBalanceToStake,
+            "TooManyRegistrationsThisBlock" | "TooManyRegistrationsThisInterval" => {
+                Self::TooManyRegistrationsThisBlock
+            }
+            "SubNetworkDoesNotExist" => Self::SubNetworkDoesNotExist,
+            "SubNetRegistrationDisabled" => Self::RegistrationDisabled,
+            _ => Self::Other,
+        }
+    }
+
+    /// Recognizes the transaction pool rejections by their message
+    ///
+    /// `Priority is too low` is the pool's error 1014. A stale nonce is reported as
+    /// `Invalid Transaction` with `Transaction is outdated`, or as a usurped transaction by the
+    /// status stream, so it is checked before the generic invalid transaction.
+    fn from_pool_message(message: &str) -> Self {
+        let message = message.to_lowercase();
+        if message.contains("priority is too low") {
+            Self::PriorityTooLow
+        } else if message.contains("outdated") || message.contains("usurped") {
+            Self::Stale
+        } else if message.contains("invalid transaction")
+            || message.contains("transaction is invalid")
+        {
+            Self::Invalid
+        } else {
+            Self::Other
+        }
+    }
+
+    /// The action taken when no other is configured
+    pub fn default_action(self) -> ErrorAction {
+        match self {
+            Self::HotKeyAlreadyRegisteredInSubNet => ErrorAction::Stop,
+            Self::NotEnoughBalanceToStake | Self::RegistrationDisabled | Self::Invalid => {
+                ErrorAction::Backoff
+            }
+            Self::TooManyRegistrationsThisBlock
+            | Self::PriorityTooLow
+            | Self::Stale
+            | Self::Other => ErrorAction::Retry,
+            Self::SubNetworkDoesNotExist => ErrorAction::Fail,
+        }
+    }
+}
+
+impl fmt::Display for DispatchErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::HotKeyAlreadyRegisteredInSubNet => "hotkey already registered",
+            Self::NotEnoughBalanceToStake => "not enough balance",
+            Self::TooManyRegistrationsThisBlock => "too many registrations",
+            Self::SubNetworkDoesNotExist => "subnet does not exist",
+            Self::RegistrationDisabled => "registration disabled",
+            Self::PriorityTooLow => "priority too low",
+            Self::Stale => "stale",
+            Self::Invalid => "invalid",
+            Self::Other => "other",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// What the registration loop does after a rejected attempt
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorAction {
+    /// Stop trying and exit without an error
+    Stop,
+    /// Try again on the next block
+    Retry,
+    /// Try again after an exponentially growing delay
+    Backoff,
+    /// Stop trying and exit with the error
+    Fail,
+}
+
+impl fmt::Display for ErrorAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ErrorAction::Stop => "stop",
+            ErrorAction::Retry => "retry",
+            ErrorAction::Backoff => "backoff",
+            ErrorAction::Fail => "fail",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Delay of the first backoff, about one block
+const BACKOFF_INITIAL: Duration = Duration::from_secs(12);
+
+/// Upper bound of the backoff delay
+const BACKOFF_MAX: Duration = Duration::from_secs(600);
+
+/// Actions per rejection class shared by the registration binaries, unset ones use the default
+#[derive(Args, Debug, Clone, Default)]
+pub struct ErrorPolicyParams {
+    /// Action when the hotkey is already registered [default: stop]
+    #[clap(long, value_enum)]
+    pub on_already_registered: Option<ErrorAction>,
+
+    /// Action when the coldkey cannot pay the burn or lock [default: backoff]
+    #[clap(long, value_enum)]
+    pub on_insufficient_balance: Option<ErrorAction>,
+
+    /// Action when the block or interval registration quota is used up [default: retry]
+    #[clap(long, value_enum)]
+    pub on_too_many_registrations: Option<ErrorAction>,
+
+    /// Action when the subnet does not exist [default: fail]
+    #[clap(long, value_enum)]
+    pub on_subnet_missing: Option<ErrorAction>,
+
+    /// Action when registration is disabled on the subnet [default: backoff]
+    #[clap(long, value_enum)]
+    pub on_registration_disabled: Option<ErrorAction>,
+
+    /// Action when the pool reports the priority too low [default: retry]
+    #[clap(long, value_enum)]
+    pub on_priority_too_low: Option<ErrorAction>,
+
+    /// Action when the extrinsic's nonce is stale [default: retry]
+    #[clap(long, value_enum)]
+    pub on_stale: Option<ErrorAction>,
+
+    /// Action when the pool rejects the extrinsic as invalid [default: backoff]
+    #[clap(long, value_enum)]
+    pub on_invalid: Option<ErrorAction>,
+
+    /// Action for any other failure [default: retry]
+    #[clap(long, value_enum)]
+    pub on_other_error: Option<ErrorAction>,
+}
+
+impl ErrorPolicyParams {
+    /// Returns the configured action for `kind`, or its default
+    pub fn action(&self, kind: DispatchErrorKind) -> ErrorAction {
+        let configured = match kind {
+            DispatchErrorKind::HotKeyAlreadyRegisteredInSubNet => self.on_already_registered,
+            DispatchErrorKind::NotEnoughBalanceToStake => self.on_insufficient_balance,
+            DispatchErrorKind::TooManyRegistrationsThisBlock => self.on_too_many_registrations,
+            DispatchErrorKind::SubNetworkDoesNotExist => self.on_subnet_missing,
+            DispatchErrorKind::RegistrationDisabled => self.on_registration_disabled,
+            DispatchErrorKind::PriorityTooLow => self.on_priority_too_low,
+            DispatchErrorKind::Stale => self.on_stale,
+            DispatchErrorKind::Invalid => self.on_invalid,
+            DispatchErrorKind::Other => self.on_other_error,
+        };
+
+        configured.unwrap_or_else(|| kind.default_action())
+    }
+
+    /// The delays of the `backoff` action, starting at one block and doubling up to 10 minutes
+    pub fn backoff(&self) -> Backoff {
+        Backoff::new(BACKOFF_INITIAL, BACKOFF_MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pool_messages_are_classified() {
+        let cases = [
+            (
+                r#"Rpc error: RPC error: ErrorObject { code: ServerError(1014), message: "Priority is too low: (4 vs 4)", data: Some(RawValue("The transaction has too low priority to replace another transaction already in the pool.")) }"#,
+                DispatchErrorKind::PriorityTooLow,
+            ),
+            (
+                r#"Rpc error: RPC error: ErrorObject { code: ServerError(1010), message: "Invalid Transaction", data: Some(RawValue("Transaction is outdated")) }"#,
+                DispatchErrorKind::Stale,
+            ),
+            (
+                "Transaction was usurped by another with the same nonce",
+                DispatchErrorKind::Stale,
+            ),
+            (
+                r#"Rpc error: RPC error: ErrorObject { code: ServerError(1010), message: "Invalid Transaction", data: Some(RawValue("Transaction has a bad signature")) }"#,
+                DispatchErrorKind::Invalid,
+            ),
+            (
+                "Transaction is invalid (eg because of a bad nonce, signature etc)",
+                DispatchErrorKind::Invalid,
+            ),
+            ("Transaction dropped", DispatchErrorKind::Other),
+        ];
+        for (message, kind) in cases {
+            assert_eq!(
+                DispatchErrorKind::from_pool_message(message),
+                kind,
+                "{}",
+                message
+            );
+        }
     }
 }
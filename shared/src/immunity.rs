@@ -0,0 +1,298 @@
+//! Immunity and pruning risk of a registered neuron.
+//!
+//! A new neuron cannot be pruned for `ImmunityPeriod` blocks after `BlockAtRegistration`. Once
+//! that ends, the next registration on a full subnet replaces the non-immune neuron with the
+//! lowest `PruningScores` entry. Neurons registered after ours are still immune when our immunity
+//! ends, so our rank is taken among the neurons registered at or before our block.
+
+use std::fmt;
+use std::time::Duration;
+use subxt::dynamic::Value;
+use subxt::utils::{AccountId32, H256};
+use subxt::{OnlineClient, SubstrateConfig};
+
+/// Immunity and pruning rank of one neuron
+#[derive(Debug, Clone)]
+pub struct ImmunityReport {
+    pub netuid: u16,
+    pub uid: u16,
+    pub hotkey: AccountId32,
+    /// The block the report was read at
+    pub current_block: u64,
+    /// The block the neuron was registered at
+    pub registered_at: u64,
+    /// The subnet's immunity period in blocks
+    pub immunity_period: u64,
+    /// The estimated block time, used to convert blocks to wall-clock time
+    pub block_time: Duration,
+    /// The neuron's current pruning score
+    pub pruning_score: u16,
+    /// 1-based position in the pruning order once the immunity ends, 1 is pruned first
+    pub pruning_rank: usize,
+    /// Other candidates with the same pruning score
+    pub pruning_ties: usize,
+    /// Neurons that can be pruned once the immunity ends, including this one
+    pub pruning_candidates: usize,
+}
+
+impl ImmunityReport {
+    /// Blocks until the immunity ends, 0 if it already has
+    pub fn immunity_remaining_blocks(&self) -> u64 {
+        (self.registered_at + self.immunity_period).saturating_sub(self.current_block)
+    }
+
+    /// Estimated wall-clock time until the immunity ends
+    pub fn immunity_remaining(&self) -> Duration {
+        self.block_time * self.immunity_remaining_blocks() as u32
+    }
+}
+
+impl fmt::Display for ImmunityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Immunity report:")?;
+        writeln!(f, "UID: {}", self.uid)?;
+        writeln!(f, "Netuid: {}", self.netuid)?;
+        writeln!(f, "Hotkey: {}", self.hotkey)?;
+        writeln!(
+            f,
+            "Registered at block: {} (current block {})",
+            self.registered_at, self.current_block
+        )?;
+        match self.immunity_remaining_blocks() {
+            0 => writeln!(
+                f,
+                "Immunity: ended at block {}",
+                self.registered_at + self.immunity_period
+            )?,
+            remaining => writeln!(
+                f,
+                "Immunity: {} of {} blocks remaining, about {} at {:?} per block",
+                remaining,
+                self.immunity_period,
+                format_duration(self.immunity_remaining()),
+                self.block_time
+            )?,
+        }
+        writeln!(f, "Pruning score: {}", self.pruning_score)?;
+        write!(
+            f,
+            "Pruning rank after immunity: {} of {} (1 is pruned first), tied with {} other(s)",
+            self.pruning_rank, self.pruning_candidates, self.pruning_ties
+        )
+    }
+}
+
+/// Reads the immunity and pruning rank of `hotkey` on `netuid`
+///
+/// # Arguments
+///
+/// * `client` - A reference to the blockchain client
+/// * `netuid` - The subnet to report on
+/// * `hotkey` - The hotkey to report on
+/// * `block_time` - The block time used for the wall-clock estimate, see
+///   [`crate::estimate_block_time`] and [`crate::recent_block_time`]
+///
+/// # Returns
+///
+/// A `Result` containing the `ImmunityReport`, `None` if the hotkey is not registered on the
+/// subnet, or an `Err` if the storage cannot be read
+pub async fn get_immunity_report(
+    client: &OnlineClient<SubstrateConfig>,
+    netuid: u16,
+    hotkey: &AccountId32,
+    block_time: Duration,
+) -> Result<Option<ImmunityReport>, Box<dyn std::error::Error>> {
+    let block = client.blocks().at_latest().await?;
+    let block_hash = block.hash();
+
+    let uid: u16 = match fetch(
+        client,
+        block_hash,
+        "Uids",
+        vec![netuid.into(), Value::from_bytes(hotkey.0)],
+    )
+    .await?
+    {
+        Some(uid) => uid,
+        None => return Ok(None),
+    };
+
+    let immunity_period: u16 = client
+        .storage()
+        .at(block_hash)
+        .fetch_or_default(&subxt::storage::dynamic(
+            "SubtensorModule",
+            "ImmunityPeriod",
+            vec![Value::from(netuid)],
+        ))
+        .await?
+        .as_type()?;
+    let pruning_scores: Vec<u16> = fetch(client, block_hash, "PruningScores", vec![netuid.into()])
+        .await?
+        .unwrap_or_default();
+
+    // Block at registration of every UID, to tell which neurons will still be immune
+    let mut registered_at = vec![0u64; pruning_scores.len()];
+    let address = subxt::storage::dynamic(
+        "SubtensorModule",
+        "BlockAtRegistration",
+        vec![Value::from(netuid)],
+    );
+    let mut entries = client.storage().at(block_hash).iter(address).await?;
+    while let Some(entry) = entries.next().await {
+        let entry = entry?;
+        let other = entry
+            .keys
+            .get(1)
+            .and_then(Value::as_u128)
+            .ok_or("BlockAtRegistration key without a UID")?;
+        if let Some(block) = registered_at.get_mut(other as usize) {
+            *block = entry.value.as_type()?;
+        }
+    }
+
+    let neurons: Vec<(u16, u16, u64)> = (0u16..)
+        .zip(pruning_scores)
+        .zip(registered_at)
+        .map(|((uid, score), block)| (uid, score, block))
+        .collect();
+    let rank = pruning_rank(&neurons, uid).ok_or("UID has no pruning score")?;
+
+    Ok(Some(ImmunityReport {
+        netuid,
+        uid,
+        hotkey: hotkey.clone(),
+        current_block: block.number().into(),
+        registered_at: rank.registered_at,
+        immunity_period: immunity_period.into(),
+        block_time,
+        pruning_score: rank.score,
+        pruning_rank: rank.rank,
+        pruning_ties: rank.ties,
+        pruning_candidates: rank.candidates,
+    }))
+}
+
+/// Position of a neuron in the pruning order once its immunity ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PruningRank {
+    /// The block the neuron was registered at
+    registered_at: u64,
+    /// The neuron's pruning score
+    score: u16,
+    /// 1-based position in the pruning order, 1 is pruned first
+    rank: usize,
+    /// Other candidates with the same pruning score
+    ties: usize,
+    /// Neurons that can be pruned once the immunity ends, including this one
+    candidates: usize,
+}
+
+/// Ranks `uid` among the neurons whose immunity has ended when its own does
+///
+/// Every neuron has the same immunity period, so the candidates are the neurons registered at or
+/// before `uid`, whether or not they are still immune now.
+///
+/// # Arguments
+///
+/// * `neurons` - The uid, pruning score and registration block of every neuron on the subnet
+/// * `uid` - The neuron to rank
+///
+/// # Returns
+///
+/// The `PruningRank` of `uid`, or `None` if it is not among `neurons`
+fn pruning_rank(neurons: &[(u16, u16, u64)], uid: u16) -> Option<PruningRank> {
+    let &(_, score, registered_at) = neurons.iter().find(|(other, _, _)| *other == uid)?;
+    let candidates: Vec<u16> = neurons
+        .iter()
+        .filter(|(other, _, block)| *other != uid && *block <= registered_at)
+        .map(|(_, score, _)| *score)
+        .collect();
+
+    Some(PruningRank {
+        registered_at,
+        score,
+        rank: 1 + candidates.iter().filter(|other| **other < score).count(),
+        ties: candidates.iter().filter(|other| **other == score).count(),
+        candidates: candidates.len() + 1,
+    })
+}
+
+/// Fetches and decodes a `SubtensorModule` storage entry at `block_hash`
+async fn fetch<T: subxt::ext::scale_decode::DecodeAsType>(
+    client: &OnlineClient<SubstrateConfig>,
+    block_hash: H256,
+    entry: &str,
+    keys: Vec<Value>,
+) -> Result<Option<T>, Box<dyn std::error::Error>> {
+    let address = subxt::storage::dynamic("SubtensorModule", entry, keys);
+    let value = client
+        .storage()
+        .at(block_hash)
+        .fetch(&address)
+        .await?
+        .map(|value| value.as_type::<T>())
+        .transpose()?;
+
+    Ok(value)
+}
+
+/// Formats a duration as hours and minutes
+fn format_duration(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lower_scores_are_pruned_first() {
+        let neurons = [(0, 50, 100), (1, 10, 100), (2, 30, 200), (3, 90, 150)];
+
+        let rank = pruning_rank(&neurons, 2).unwrap();
+        assert_eq!(rank.registered_at, 200);
+        assert_eq!(rank.score, 30);
+        assert_eq!(rank.rank, 2);
+        assert_eq!(rank.ties, 0);
+        assert_eq!(rank.candidates, 4);
+    }
+
+    #[test]
+    fn equal_scores_are_ties() {
+        let neurons = [(0, 30, 100), (1, 30, 100), (2, 30, 200), (3, 10, 100)];
+
+        let rank = pruning_rank(&neurons, 2).unwrap();
+        assert_eq!(rank.rank, 2);
+        assert_eq!(rank.ties, 2);
+        assert_eq!(rank.candidates, 4);
+    }
+
+    #[test]
+    fn neurons_registered_later_are_not_candidates() {
+        // UIDs 1 and 2 are registered after UID 0 and are still immune when its immunity ends
+        let neurons = [(0, 30, 100), (1, 0, 101), (2, 10, 500)];
+
+        let rank = pruning_rank(&neurons, 0).unwrap();
+        assert_eq!(rank.rank, 1);
+        assert_eq!(rank.ties, 0);
+        assert_eq!(rank.candidates, 1);
+    }
+
+    #[test]
+    fn neurons_still_immune_now_are_candidates_if_registered_earlier() {
+        // With a 1000 block immunity period at block 1050, UIDs 0 and 1 are both still immune,
+        // but UID 0's immunity ends first
+        let neurons = [(0, 5, 900), (1, 20, 1000), (2, 1, 1040)];
+
+        let rank = pruning_rank(&neurons, 1).unwrap();
+        assert_eq!(rank.rank, 2);
+        assert_eq!(rank.candidates, 2);
+    }
+
+    #[test]
+    fn unknown_uid_has_no_rank() {
+        assert!(pruning_rank(&[(0, 30, 100)], 1).is_none());
+    }
+}
@@ -1,9 +1,16 @@
-mod errors;
+pub mod broadcast;
+pub mod budget;
+pub mod errors;
+pub mod immunity;
+pub mod resilient;
+pub mod tx;
+pub mod wallet;
 
 use crate::errors::Error;
-use clap::Parser;
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, Command, Parser};
 use log::{error, info, warn};
-use serde::de::DeserializeOwned;
+use std::ffi::OsString;
 use std::fs;
 use std::time::{Duration, Instant};
 use subxt::{OnlineClient, SubstrateConfig};
@@ -24,8 +31,9 @@ use subxt::{OnlineClient, SubstrateConfig};
 ///
 /// # Example
 ///
-/// ```
-/// use subxt::OnlineClient;
+/// ```no_run
+/// use shared::estimate_block_time;
+/// use subxt::{OnlineClient, SubstrateConfig};
 ///
 /// #[tokio::main]
 /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -60,14 +68,14 @@ pub async fn estimate_block_time(
         let header = backend
             .block_header(block_ref.hash())
             .await?
-            .ok_or_else(|| Error::Other("Block header not found".into()))?;
+            .ok_or(Error::BlockHeaderNotFound)?;
         header.number < target_block_number
     } {
         tokio::time::sleep(Duration::from_millis(100)).await;
 
         // Check if we've exceeded the maximum wait time
         if start_time.elapsed() > MAX_WAIT_TIME {
-            return Err("Exceeded maximum wait time for block sampling".into());
+            return Err(Error::ExceededMaxWaitTime.into());
         }
     }
 
@@ -90,27 +98,286 @@ pub async fn estimate_block_time(
     Ok(estimated_block_time)
 }
 
+/// Estimates the block time from the `Timestamp::Now` of the latest finalized block and of the
+/// block `sample_size` blocks before it.
+///
+/// Unlike [`estimate_block_time`] this does not wait for new blocks, so it returns at once,
+/// but it reflects past rather than current conditions.
+///
+/// # Arguments
+///
+/// * `client` - A reference to an `OnlineClient<SubstrateConfig>` used to interact with the blockchain.
+/// * `sample_size` - The number of past blocks to average over, at most the `BlockHashCount`.
+///
+/// # Returns
+///
+/// * `Result<Duration, Box<dyn std::error::Error>>` - The estimated block time or an error.
+pub async fn recent_block_time(
+    client: &OnlineClient<SubstrateConfig>,
+    sample_size: u32,
+) -> Result<Duration, Box<dyn std::error::Error>> {
+    let latest = client.blocks().at_latest().await?;
+    let sample_size = sample_size.clamp(1, latest.number().max(1));
+    let storage = client.storage().at(latest.hash());
+
+    let start_hash: subxt::utils::H256 = storage
+        .fetch(&subxt::storage::dynamic(
+            "System",
+            "BlockHash",
+            vec![subxt::dynamic::Value::from(
+                latest.number().saturating_sub(sample_size),
+            )],
+        ))
+        .await?
+        .ok_or(Error::BlockHeaderNotFound)?
+        .as_type()?;
+    let now = subxt::storage::dynamic("Timestamp", "Now", ());
+    let end_ms: u64 = storage.fetch_or_default(&now).await?.as_type()?;
+    let start_ms: u64 = client
+        .storage()
+        .at(start_hash)
+        .fetch_or_default(&now)
+        .await?
+        .as_type()?;
+
+    let block_time = Duration::from_millis(end_ms.saturating_sub(start_ms) / sample_size as u64);
+    info!(
+        "Recent block time: {:?} (based on the last {} blocks)",
+        block_time, sample_size
+    );
+
+    Ok(block_time)
+}
+
 // TODO: Consider implementing a more sophisticated estimation algorithm that accounts for
 // network congestion and temporary fluctuations in block production times.
 
 // TODO: Add unit tests to verify the function's behavior under various conditions,
 // including simulated network delays and edge cases.
 
-pub fn parse_config<T: Parser + DeserializeOwned>() -> Result<T, Box<dyn std::error::Error>> {
+/// Parses the parameters from `config.toml`, if it exists, and the command line
+///
+/// Every key of `config.toml` is turned into its command-line argument and placed before the
+/// actual arguments, so both are validated by the same parser. Keys whose argument is also given
+/// on the command line are left out, so the command line replaces the config value, including
+/// lists. Every flag gets a `--no-<flag>` counterpart to switch off a `true` from the config
+/// file. Keys without a matching argument are ignored with a warning.
+///
+/// # Returns
+///
+/// A `Result` containing the parameters, or an `Err` if `config.toml` cannot be parsed or the
+/// arguments are invalid
+pub fn parse_config<T: Parser>() -> Result<T, Box<dyn std::error::Error>> {
+    let command = with_negations(T::command());
+    let mut args: Vec<OsString> = std::env::args_os().collect();
     if let Ok(config_str) = fs::read_to_string("config.toml") {
         info!("Found config.toml, parsing...");
-        match toml::from_str(&config_str) {
-            Ok(params) => {
-                info!("Successfully parsed config.toml");
-                Ok(params)
-            }
-            Err(e) => {
-                error!("Error parsing config.toml: {}", e);
-                Err(Box::new(e))
-            }
-        }
+        let config: toml::Table = toml::from_str(&config_str).map_err(|e| {
+            error!("Error parsing config.toml: {}", e);
+            e
+        })?;
+        args = merge_config_args(&command, &config, args)?;
+        info!("Successfully parsed config.toml");
     } else {
         info!("No config.toml found, parsing command line arguments...");
-        Ok(T::parse())
+    }
+
+    let matches = command.get_matches_from(args);
+
+    Ok(T::from_arg_matches(&matches)?)
+}
+
+/// Adds a `--no-<flag>` argument for every flag of `command`
+///
+/// The flag and its negation override each other, so the last one given wins.
+///
+/// # Arguments
+///
+/// * `command` - The command to add the negations to
+///
+/// # Returns
+///
+/// The command with the negations
+fn with_negations(mut command: Command) -> Command {
+    let flags: Vec<(String, String)> = command
+        .get_arguments()
+        .filter(|arg| matches!(arg.get_action(), ArgAction::SetTrue))
+        .filter_map(|arg| Some((arg.get_id().to_string(), arg.get_long()?.to_string())))
+        .collect();
+    for (id, long) in flags {
+        let negation = format!("no_{}", id);
+        command = command
+            .mut_arg(&id, |arg| arg.overrides_with(&negation))
+            .arg(
+                Arg::new(&negation)
+                    .long(format!("no-{}", long))
+                    .action(ArgAction::SetTrue)
+                    .overrides_with(&id)
+                    .help(format!("Turns off --{}", long)),
+            );
+    }
+
+    command
+}
+
+/// Places the arguments from a config file before the command-line arguments
+///
+/// Config keys whose argument, or its negation, is given on the command line are left out.
+///
+/// # Arguments
+///
+/// * `command` - The command the arguments are for
+/// * `config` - The parsed config file
+/// * `args` - The command-line arguments, starting with the binary name
+///
+/// # Returns
+///
+/// A `Result` containing the merged arguments, or an `Err` if a value is a table or a date
+fn merge_config_args(
+    command: &Command,
+    config: &toml::Table,
+    mut args: Vec<OsString>,
+) -> Result<Vec<OsString>, Box<dyn std::error::Error>> {
+    // A lenient parse of the command line alone, only to see which arguments it sets
+    let given = command
+        .clone()
+        .ignore_errors(true)
+        .try_get_matches_from(&args)
+        .ok();
+    let on_command_line = |id: &str| {
+        let Some(matches) = &given else {
+            return false;
+        };
+        [id.to_string(), format!("no_{}", id)].iter().any(|id| {
+            command
+                .get_arguments()
+                .any(|arg| arg.get_id() == id.as_str())
+                && matches.value_source(id) == Some(ValueSource::CommandLine)
+        })
+    };
+
+    let config: toml::Table = config
+        .iter()
+        .filter(|(key, _)| !on_command_line(key))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    args.splice(1..1, config_args(command, &config)?);
+
+    Ok(args)
+}
+
+/// Converts the keys of a config file to command-line arguments of `command`
+///
+/// # Arguments
+///
+/// * `command` - The command the arguments are for
+/// * `config` - The parsed config file
+///
+/// # Returns
+///
+/// A `Result` containing the arguments, or an `Err` if a value is a table or a date
+fn config_args(
+    command: &Command,
+    config: &toml::Table,
+) -> Result<Vec<OsString>, Box<dyn std::error::Error>> {
+    let mut args = Vec::new();
+    for (key, value) in config {
+        let Some(long) = command
+            .get_arguments()
+            .find(|arg| arg.get_id() == key.as_str())
+            .and_then(|arg| arg.get_long())
+        else {
+            warn!("Ignoring unknown key {} in config.toml", key);
+            continue;
+        };
+        let flag = OsString::from(format!("--{}", long));
+
+        let values = match value {
+            toml::Value::Boolean(true) => {
+                args.push(flag);
+                continue;
+            }
+            toml::Value::Boolean(false) => continue,
+            toml::Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            let value = match value {
+                toml::Value::String(value) => value.clone(),
+                toml::Value::Integer(value) => value.to_string(),
+                toml::Value::Float(value) => value.to_string(),
+                _ => return Err(format!("Unsupported value for {} in config.toml", key).into()),
+            };
+            args.push(flag.clone());
+            args.push(value.into());
+        }
+    }
+
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{CommandFactory, FromArgMatches};
+
+    #[derive(Parser, Debug)]
+    struct Params {
+        #[clap(long)]
+        netuid: u16,
+
+        #[clap(long)]
+        max_cost_tao: Option<String>,
+
+        #[clap(long)]
+        dry_run: bool,
+
+        #[clap(long, value_delimiter = ',')]
+        endpoints: Vec<String>,
+    }
+
+    fn parse(config: &str, cli: &[&str]) -> Params {
+        let config: toml::Table = toml::from_str(config).unwrap();
+        let command = with_negations(Params::command());
+        let mut args: Vec<OsString> = vec!["params".into()];
+        args.extend(cli.iter().map(OsString::from));
+        let args = merge_config_args(&command, &config, args).unwrap();
+        Params::from_arg_matches(&command.get_matches_from(args)).unwrap()
+    }
+
+    #[test]
+    fn command_line_overrides_config() {
+        let params = parse(
+            "netuid = 12\nmax_cost_tao = \"1.5\"\nendpoints = [\"a\", \"b\"]\nunknown = 1",
+            &["--netuid", "3", "--dry-run"],
+        );
+        assert_eq!(params.netuid, 3);
+        assert_eq!(params.max_cost_tao.as_deref(), Some("1.5"));
+        assert!(params.dry_run);
+        assert_eq!(params.endpoints, ["a", "b"]);
+    }
+
+    #[test]
+    fn command_line_replaces_config_lists() {
+        let params = parse(
+            "netuid = 1\nendpoints = [\"a\", \"b\"]",
+            &["--endpoints", "c,d"],
+        );
+        assert_eq!(params.endpoints, ["c", "d"]);
+    }
+
+    #[test]
+    fn false_flags_are_left_out() {
+        let params = parse("netuid = 1\ndry_run = false", &[]);
+        assert!(!params.dry_run);
+    }
+
+    #[test]
+    fn negated_flags_turn_off_config() {
+        let params = parse("netuid = 1\ndry_run = true", &["--no-dry-run"]);
+        assert!(!params.dry_run);
+
+        let params = parse("netuid = 1", &["--no-dry-run", "--dry-run"]);
+        assert!(params.dry_run);
     }
 }
@@ -0,0 +1,196 @@
+//! A blockchain connection that survives websocket drops.
+//!
+//! Subscriptions and requests on a dropped websocket fail for good, which used to end the
+//! long-running loops. `ResilientClient` keeps the endpoint around and rebuilds the RPC and
+//! `OnlineClient` connection with exponential backoff. Callers keep their loop state outside the
+//! connection, check failures with `is_connection_error`, reconnect, and re-create their
+//! subscriptions from the fresh clients.
+
+use crate::errors::Error;
+use log::{info, warn};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use subxt::backend::rpc::RpcClient;
+use subxt::error::RpcError;
+use subxt::{OnlineClient, SubstrateConfig};
+
+/// Delay before the first reconnection attempt
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Upper bound of the delay between reconnection attempts
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Exponentially growing delay between retries
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    next: Duration,
+    max: Duration,
+}
+
+impl Backoff {
+    /// Creates a backoff starting at `initial` and doubling up to `max`
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self { next: initial, max }
+    }
+
+    /// Returns the delay to wait before the next retry and doubles the following one
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.next;
+        self.next = (self.next * 2).min(self.max);
+        delay
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(INITIAL_BACKOFF, MAX_BACKOFF)
+    }
+}
+
+/// The clients of one websocket connection
+struct Connection {
+    rpc: RpcClient,
+    client: OnlineClient<SubstrateConfig>,
+}
+
+/// A connection to a single endpoint that can be rebuilt after it drops
+///
+/// Cloning is cheap and all clones share the same connection, so a reconnect by one user is
+/// picked up by the others.
+#[derive(Clone)]
+pub struct ResilientClient {
+    endpoint: String,
+    connection: Arc<RwLock<Connection>>,
+}
+
+impl ResilientClient {
+    /// Connects to `endpoint`
+    ///
+    /// The first connection is not retried, so a wrong endpoint fails fast.
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - The websocket URL of the node
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ResilientClient`, or an `Err` if the node cannot be reached
+    pub async fn connect(endpoint: &str) -> Result<Self, Error> {
+        let connection = open(endpoint).await?;
+
+        Ok(Self {
+            endpoint: endpoint.to_string(),
+            connection: Arc::new(RwLock::new(connection)),
+        })
+    }
+
+    /// Wraps clients that are already connected to `endpoint`
+    #[cfg(test)]
+    pub(crate) fn from_clients(
+        endpoint: &str,
+        rpc: RpcClient,
+        client: OnlineClient<SubstrateConfig>,
+    ) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            connection: Arc::new(RwLock::new(Connection { rpc, client })),
+        }
+    }
+
+    /// The endpoint this client connects to
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// The current blockchain client
+    pub fn client(&self) -> OnlineClient<SubstrateConfig> {
+        self.read().client.clone()
+    }
+
+    /// The current raw RPC client, sharing the websocket with `client()`
+    pub fn rpc(&self) -> RpcClient {
+        self.read().rpc.clone()
+    }
+
+    /// Rebuilds the connection, retrying with exponential backoff until the node is reachable
+    ///
+    /// Clients and subscriptions obtained before the call belong to the dropped connection and
+    /// have to be re-created from `client()` and `rpc()`.
+    pub async fn reconnect(&self) {
+        let mut backoff = Backoff::default();
+        loop {
+            let delay = backoff.next_delay();
+            warn!("🔌 Reconnecting to {} in {:?}...", self.endpoint, delay);
+            tokio::time::sleep(delay).await;
+
+            match open(&self.endpoint).await {
+                Ok(connection) => {
+                    *self
+                        .connection
+                        .write()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner()) = connection;
+                    info!("🔌 Reconnected to {}", self.endpoint);
+                    return;
+                }
+                Err(e) => warn!("Reconnection to {} failed: {}", self.endpoint, e),
+            }
+        }
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, Connection> {
+        self.connection
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Opens a websocket to `endpoint` and builds both clients on it
+async fn open(endpoint: &str) -> Result<Connection, Error> {
+    let rpc = RpcClient::from_url(endpoint).await?;
+    let client = OnlineClient::<SubstrateConfig>::from_rpc_client(rpc.clone()).await?;
+
+    Ok(Connection { rpc, client })
+}
+
+/// Checks whether `error` means the connection to the node was lost
+///
+/// Transport failures, dropped subscriptions, request timeouts and `Error::Disconnected` are
+/// connection errors. Errors returned by the node itself, such as an invalid transaction, are not.
+///
+/// # Arguments
+///
+/// * `error` - The error to classify
+///
+/// # Returns
+///
+/// `true` if reconnecting may resolve the error, `false` otherwise
+pub fn is_connection_error(error: &(dyn std::error::Error + 'static)) -> bool {
+    if let Some(error) = error.downcast_ref::<Error>() {
+        return match error {
+            Error::Disconnected(_) => true,
+            Error::Subxt(e) => is_subxt_connection_error(e),
+            _ => false,
+        };
+    }
+
+    error
+        .downcast_ref::<subxt::Error>()
+        .is_some_and(is_subxt_connection_error)
+}
+
+fn is_subxt_connection_error(error: &subxt::Error) -> bool {
+    match error {
+        subxt::Error::Io(_) => true,
+        subxt::Error::Rpc(RpcError::SubscriptionDropped)
+        | subxt::Error::Rpc(RpcError::DisconnectedWillReconnect(_)) => true,
+        subxt::Error::Rpc(RpcError::ClientError(e)) => matches!(
+            e.downcast_ref::<jsonrpsee::core::ClientError>(),
+            Some(
+                jsonrpsee::core::ClientError::Transport(_)
+                    | jsonrpsee::core::ClientError::RestartNeeded(_)
+                    | jsonrpsee::core::ClientError::RequestTimeout
+            )
+        ),
+        _ => false,
+    }
+}
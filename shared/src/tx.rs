@@ -0,0 +1,367 @@
+//! Helpers for building and inspecting extrinsics before they are submitted.
+
+use clap::{Args, ValueEnum};
+use log::warn;
+use std::fmt;
+use subxt::backend::legacy::rpc_methods::DryRunResult;
+use subxt::backend::legacy::LegacyRpcMethods;
+use subxt::backend::rpc::RpcClient;
+use subxt::blocks::ExtrinsicEvents;
+use subxt::config::substrate::SubstrateExtrinsicParamsBuilder;
+use subxt::config::ExtrinsicParams;
+use subxt::ext::sp_core::blake2_256;
+use subxt::tx::{Payload, Signer, ValidationResult};
+use subxt::utils::H256;
+use subxt::{OnlineClient, SubstrateConfig};
+
+/// Signing parameters of an extrinsic for the default substrate config
+pub type TxParams = <<SubstrateConfig as subxt::Config>::ExtrinsicParams as ExtrinsicParams<
+    SubstrateConfig,
+>>::Params;
+
+/// How the tip grows with each failed registration attempt
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TipEscalation {
+    /// Always tip `tip_tao`
+    #[default]
+    Fixed,
+    /// Add `tip_step_tao` per failed attempt
+    Linear,
+    /// Add `tip_step_tao` per failed attempt, up to `tip_cap_tao`
+    Capped,
+}
+
+/// Tip settings shared by the registration binaries
+#[derive(Args, Debug, Clone, Default)]
+pub struct TipParams {
+    /// Tip added to the registration extrinsic, in TAO
+    #[clap(long)]
+    pub tip_tao: Option<String>,
+
+    /// How the tip grows with each failed attempt
+    #[clap(long, value_enum, default_value_t = TipEscalation::Fixed)]
+    pub tip_escalation: TipEscalation,
+
+    /// Tip increase per failed attempt for the linear and capped policies, in TAO
+    #[clap(long)]
+    pub tip_step_tao: Option<String>,
+
+    /// Maximum tip for the capped policy, in TAO
+    #[clap(long)]
+    pub tip_cap_tao: Option<String>,
+}
+
+/// A validated tip escalation policy in RAO
+#[derive(Debug, Clone, Copy)]
+pub struct TipPolicy {
+    /// Tip of the first attempt
+    pub base_rao: u64,
+    pub escalation: TipEscalation,
+    /// Tip increase per failed attempt
+    pub step_rao: u64,
+    /// Maximum tip, `u64::MAX` unless capped
+    pub cap_rao: u64,
+}
+
+impl TipParams {
+    /// Converts the tip settings to a `TipPolicy`
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `TipPolicy`, or an `Err` if an amount is invalid or a setting
+    /// required by the escalation policy is missing
+    pub fn policy(&self) -> Result<TipPolicy, Box<dyn std::error::Error>> {
+        let base_rao = self.tip_tao.as_deref().map(tao_to_rao).transpose()?;
+        let step_rao = self.tip_step_tao.as_deref().map(tao_to_rao).transpose()?;
+        let cap_rao = self.tip_cap_tao.as_deref().map(tao_to_rao).transpose()?;
+
+        if self.tip_escalation != TipEscalation::Fixed && step_rao.is_none() {
+            return Err("tip_step_tao is required for the linear and capped tip escalation".into());
+        }
+        if self.tip_escalation == TipEscalation::Capped && cap_rao.is_none() {
+            return Err("tip_cap_tao is required for the capped tip escalation".into());
+        }
+
+        Ok(TipPolicy {
+            base_rao: base_rao.unwrap_or(0),
+            escalation: self.tip_escalation,
+            step_rao: step_rao.unwrap_or(0),
+            cap_rao: cap_rao.unwrap_or(u64::MAX),
+        })
+    }
+}
+
+impl TipPolicy {
+    /// Returns the tip for the next attempt after `failed_attempts` failed ones
+    pub fn tip_rao(&self, failed_attempts: u32) -> u64 {
+        let escalated = self
+            .base_rao
+            .saturating_add(self.step_rao.saturating_mul(failed_attempts as u64));
+
+        match self.escalation {
+            TipEscalation::Fixed => self.base_rao,
+            TipEscalation::Linear => escalated,
+            TipEscalation::Capped => escalated.min(self.cap_rao),
+        }
+    }
+
+    /// Returns the tip for the next attempt, reduced to what `max_cost` leaves after the burn
+    /// and the fee
+    ///
+    /// # Arguments
+    ///
+    /// * `failed_attempts` - The number of failed attempts so far
+    /// * `headroom_rao` - The max cost minus the burn and the fee
+    pub fn clamped_tip_rao(&self, failed_attempts: u32, headroom_rao: u64) -> u64 {
+        self.tip_rao(failed_attempts).min(headroom_rao)
+    }
+}
+
+/// Builds the signing parameters for an extrinsic carrying `tip_rao`
+pub fn tip_params(tip_rao: u64) -> TxParams {
+    SubstrateExtrinsicParamsBuilder::new()
+        .tip(tip_rao as u128)
+        .build()
+}
+
+/// Converts a decimal TAO amount to RAO
+///
+/// # Arguments
+///
+/// * `tao` - The amount in TAO, e.g. `"0.25"`
+///
+/// # Returns
+///
+/// A `Result` containing the amount in RAO, or an `Err` if `tao` is not a non-negative number
+pub fn tao_to_rao(tao: &str) -> Result<u64, Box<dyn std::error::Error>> {
+    let tao: f64 = tao.trim().parse()?;
+    if !tao.is_finite() || tao < 0.0 {
+        return Err(format!("Invalid TAO amount: {}", tao).into());
+    }
+
+    Ok((tao * 1e9).round() as u64)
+}
+
+/// Estimates the transaction fee of `call` when signed by `signer`
+///
+/// The extrinsic is signed but not submitted, and the fee is read from
+/// `TransactionPaymentApi_query_info` at the latest finalized block.
+///
+/// # Arguments
+///
+/// * `client` - A reference to the blockchain client
+/// * `call` - The call to estimate the fee for
+/// * `signer` - The account that would sign and pay for the extrinsic
+///
+/// # Returns
+///
+/// A `Result` containing the estimated fee in RAO, excluding any tip, or an `Err` if the estimation fails
+pub async fn estimate_fee<Call, S>(
+    client: &OnlineClient<SubstrateConfig>,
+    call: &Call,
+    signer: &S,
+) -> Result<u64, Box<dyn std::error::Error>>
+where
+    Call: Payload,
+    S: Signer<SubstrateConfig>,
+{
+    let extrinsic = client
+        .tx()
+        .create_signed(call, signer, Default::default())
+        .await?;
+    let fee = extrinsic.partial_fee_estimate().await?;
+
+    Ok(u64::try_from(fee)?)
+}
+
+/// Result of signing and validating an extrinsic without broadcasting it
+#[derive(Debug)]
+pub struct DryRunReport {
+    /// Account nonce the extrinsic was signed with
+    pub nonce: u64,
+    /// Tip the extrinsic was signed with, in RAO
+    pub tip_rao: u64,
+    /// Estimated transaction fee in RAO, excluding any tip
+    pub fee_rao: u64,
+    /// Hash of the signed extrinsic
+    pub extrinsic_hash: H256,
+    /// Result of `TaggedTransactionQueue_validate_transaction`
+    pub validation: ValidationResult,
+    /// Result of `system_dryRun`, `None` if the node does not expose the RPC
+    pub dispatch: Option<DryRunResult>,
+}
+
+impl fmt::Display for DryRunReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Nonce: {}", self.nonce)?;
+        writeln!(f, "Tip: {:.9} TAO", self.tip_rao as f64 / 1e9)?;
+        writeln!(f, "Estimated fee: {:.9} TAO", self.fee_rao as f64 / 1e9)?;
+        writeln!(f, "Extrinsic hash: {:?}", self.extrinsic_hash)?;
+        writeln!(f, "Validation: {:?}", self.validation)?;
+        match &self.dispatch {
+            Some(dispatch) => write!(f, "Dry run: {:?}", dispatch),
+            None => write!(f, "Dry run: system_dryRun is not available on this node"),
+        }
+    }
+}
+
+/// Signs `call` and checks it against the chain without broadcasting it
+///
+/// The signed extrinsic is validated with `TaggedTransactionQueue_validate_transaction` and,
+/// when the node allows it, executed with `system_dryRun` at the latest finalized block.
+///
+/// # Arguments
+///
+/// * `client` - A reference to the blockchain client
+/// * `rpc` - The RPC client used for `system_dryRun`
+/// * `call` - The call to check
+/// * `signer` - The account that would sign and pay for the extrinsic
+/// * `tip_rao` - The tip to sign the extrinsic with
+///
+/// # Returns
+///
+/// A `Result` containing the `DryRunReport`, or an `Err` if the extrinsic could not be built or validated
+pub async fn dry_run<Call, S>(
+    client: &OnlineClient<SubstrateConfig>,
+    rpc: &RpcClient,
+    call: &Call,
+    signer: &S,
+    tip_rao: u64,
+) -> Result<DryRunReport, Box<dyn std::error::Error>>
+where
+    Call: Payload,
+    S: Signer<SubstrateConfig>,
+{
+    let nonce = client.tx().account_nonce(&signer.account_id()).await?;
+    let extrinsic = client
+        .tx()
+        .create_signed(call, signer, tip_params(tip_rao))
+        .await?;
+    let fee_rao = u64::try_from(extrinsic.partial_fee_estimate().await?)?;
+    let validation = extrinsic.validate().await?;
+
+    let finalized = client.backend().latest_finalized_block_ref().await?;
+    let dispatch = match LegacyRpcMethods::<SubstrateConfig>::new(rpc.clone())
+        .dry_run(extrinsic.encoded(), Some(finalized.hash()))
+        .await
+    {
+        Ok(bytes) => Some(bytes.into_dry_run_result(&client.metadata())?),
+        Err(e) => {
+            warn!("system_dryRun is not available: {}", e);
+            None
+        }
+    };
+
+    Ok(DryRunReport {
+        nonce,
+        tip_rao,
+        fee_rao,
+        extrinsic_hash: extrinsic.hash(),
+        validation,
+        dispatch,
+    })
+}
+
+/// Searches the finalized block `block_number` for the extrinsic `extrinsic_hash`
+///
+/// # Arguments
+///
+/// * `client` - A reference to the blockchain client
+/// * `rpc_methods` - The RPC methods, used to look up the block hash
+/// * `block_number` - The block to search
+/// * `extrinsic_hash` - Hash of the extrinsic to look for
+///
+/// # Returns
+///
+/// A `Result` containing the block hash and the events of the extrinsic if the block includes
+/// it, `None` otherwise, or an `Err` if the block cannot be fetched
+pub async fn find_extrinsic(
+    client: &OnlineClient<SubstrateConfig>,
+    rpc_methods: &LegacyRpcMethods<SubstrateConfig>,
+    block_number: u64,
+    extrinsic_hash: H256,
+) -> Result<Option<(H256, ExtrinsicEvents<SubstrateConfig>)>, Box<dyn std::error::Error>> {
+    let block_hash = rpc_methods
+        .chain_get_block_hash(Some(block_number.into()))
+        .await?
+        .ok_or_else(|| format!("Block {} not found", block_number))?;
+    let extrinsics = client.blocks().at(block_hash).await?.extrinsics().await?;
+
+    for extrinsic in extrinsics.iter() {
+        let extrinsic = extrinsic?;
+        if H256(blake2_256(extrinsic.bytes())) == extrinsic_hash {
+            return Ok(Some((block_hash, extrinsic.events().await?)));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(escalation: TipEscalation) -> TipPolicy {
+        TipPolicy {
+            base_rao: 1_000_000,
+            escalation,
+            step_rao: 500_000,
+            cap_rao: 2_000_000,
+        }
+    }
+
+    #[test]
+    fn fixed_tip_does_not_grow() {
+        let policy = policy(TipEscalation::Fixed);
+        assert_eq!(policy.tip_rao(0), 1_000_000);
+        assert_eq!(policy.tip_rao(10), 1_000_000);
+    }
+
+    #[test]
+    fn linear_tip_grows_per_failed_attempt() {
+        let policy = policy(TipEscalation::Linear);
+        assert_eq!(policy.tip_rao(0), 1_000_000);
+        assert_eq!(policy.tip_rao(3), 2_500_000);
+        assert_eq!(
+            policy.tip_rao(u32::MAX),
+            1_000_000 + 500_000 * u32::MAX as u64
+        );
+    }
+
+    #[test]
+    fn capped_tip_stops_at_the_cap() {
+        let policy = policy(TipEscalation::Capped);
+        assert_eq!(policy.tip_rao(1), 1_500_000);
+        assert_eq!(policy.tip_rao(2), 2_000_000);
+        assert_eq!(policy.tip_rao(3), 2_000_000);
+    }
+
+    #[test]
+    fn tip_is_clamped_to_the_max_cost() {
+        let policy = policy(TipEscalation::Linear);
+        assert_eq!(policy.clamped_tip_rao(3, 10_000_000), 2_500_000);
+        assert_eq!(policy.clamped_tip_rao(3, 1_200_000), 1_200_000);
+        assert_eq!(policy.clamped_tip_rao(0, 0), 0);
+    }
+
+    #[test]
+    fn escalation_requires_its_settings() {
+        let params = TipParams {
+            tip_tao: Some("0.001".to_string()),
+            tip_escalation: TipEscalation::Capped,
+            tip_step_tao: Some("0.0005".to_string()),
+            tip_cap_tao: None,
+        };
+        assert!(params.policy().is_err());
+
+        let policy = TipParams {
+            tip_cap_tao: Some("0.002".to_string()),
+            ..params
+        }
+        .policy()
+        .unwrap();
+        assert_eq!(policy.base_rao, 1_000_000);
+        assert_eq!(policy.step_rao, 500_000);
+        assert_eq!(policy.cap_rao, 2_000_000);
+    }
+}
@@ -0,0 +1,246 @@
+//! Loading of keypairs from a Bittensor wallet directory.
+//!
+//! A wallet lives in `<wallet_path>/<name>/` and contains an encrypted `coldkey` keyfile and
+//! one keyfile per hotkey under `hotkeys/<hotkey>`. Keyfiles are JSON documents holding the
+//! secret seed and phrase; encrypted keyfiles are prefixed with `$NACL` and use the same
+//! argon2i + XSalsa20-Poly1305 scheme as the Python wallet.
+
+use crate::errors::Error;
+use argon2::{Algorithm, Argon2, Params, Version};
+use crypto_secretbox::aead::{Aead, KeyInit};
+use crypto_secretbox::{Key, Nonce, XSalsa20Poly1305};
+use log::info;
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+use subxt::ext::sp_core::{sr25519, Pair};
+
+/// Default location of Bittensor wallets
+pub const DEFAULT_WALLET_PATH: &str = "~/.bittensor/wallets";
+
+/// Environment variable read for the coldkey password before prompting
+pub const PASSWORD_ENV_VAR: &str = "BT_WALLET_PASSWORD";
+
+const NACL_PREFIX: &[u8] = b"$NACL";
+const NACL_SALT: &[u8] = b"\x13q\x83\xdf\xf1Z\t\xbc\x9c\x90\xb5Q\x879\xe9\xb1";
+const NONCE_SIZE: usize = 24;
+// libsodium's OPSLIMIT_SENSITIVE and MEMLIMIT_SENSITIVE (in KiB) for argon2i
+const ARGON2_T_COST: u32 = 8;
+const ARGON2_M_COST: u32 = 524_288;
+
+/// Contents of a decrypted keyfile
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeyfileData {
+    secret_seed: Option<String>,
+    secret_phrase: Option<String>,
+}
+
+/// A Bittensor wallet on disk
+#[derive(Debug, Clone)]
+pub struct Wallet {
+    name: String,
+    path: PathBuf,
+}
+
+impl Wallet {
+    /// Creates a wallet handle for `name`, located under `path` or the default wallet path
+    pub fn new(name: &str, path: Option<&str>) -> Self {
+        let root = expand_home(path.unwrap_or(DEFAULT_WALLET_PATH));
+        Self {
+            name: name.to_string(),
+            path: root.join(name),
+        }
+    }
+
+    /// Loads the coldkey of the wallet, asking for the password if the keyfile is encrypted
+    ///
+    /// The password is read from `BT_WALLET_PASSWORD` if set, otherwise it is prompted for.
+    pub fn coldkey(&self) -> Result<sr25519::Pair, Error> {
+        load_keyfile(self.path.join("coldkey"), &format!("coldkey {}", self.name))
+    }
+
+    /// Loads the hotkey `hotkey` of the wallet
+    pub fn hotkey(&self, hotkey: &str) -> Result<sr25519::Pair, Error> {
+        load_keyfile(
+            self.path.join("hotkeys").join(hotkey),
+            &format!("hotkey {}/{}", self.name, hotkey),
+        )
+    }
+}
+
+/// Resolves the coldkey from either a mnemonic/seed or a wallet
+///
+/// # Arguments
+///
+/// * `secret` - A mnemonic phrase or seed, takes precedence over the wallet
+/// * `wallet_name` - The name of the wallet to load the coldkey from
+/// * `wallet_path` - The directory containing the wallets, defaults to `~/.bittensor/wallets`
+///
+/// # Returns
+///
+/// A `Result` containing the coldkey pair, or an `Err` if neither source is usable
+pub fn load_coldkey(
+    secret: Option<&str>,
+    wallet_name: Option<&str>,
+    wallet_path: Option<&str>,
+) -> Result<sr25519::Pair, Error> {
+    match (secret, wallet_name) {
+        (Some(secret), _) => pair_from_secret(secret, "coldkey"),
+        (None, Some(name)) => Wallet::new(name, wallet_path).coldkey(),
+        (None, None) => Err(Error::Keyfile(
+            "Either a coldkey or a wallet name must be provided".into(),
+        )),
+    }
+}
+
+/// Resolves the hotkey from either a mnemonic/seed or a wallet
+///
+/// # Arguments
+///
+/// * `secret` - A mnemonic phrase or seed, takes precedence over the wallet
+/// * `wallet_name` - The name of the wallet holding the hotkey
+/// * `wallet_hotkey` - The name of the hotkey inside the wallet
+/// * `wallet_path` - The directory containing the wallets, defaults to `~/.bittensor/wallets`
+///
+/// # Returns
+///
+/// A `Result` containing the hotkey pair, or an `Err` if neither source is usable
+pub fn load_hotkey(
+    secret: Option<&str>,
+    wallet_name: Option<&str>,
+    wallet_hotkey: Option<&str>,
+    wallet_path: Option<&str>,
+) -> Result<sr25519::Pair, Error> {
+    match (secret, wallet_name, wallet_hotkey) {
+        (Some(secret), _, _) => pair_from_secret(secret, "hotkey"),
+        (None, Some(name), Some(hotkey)) => Wallet::new(name, wallet_path).hotkey(hotkey),
+        _ => Err(Error::Keyfile(
+            "Either a hotkey or a wallet name and wallet hotkey must be provided".into(),
+        )),
+    }
+}
+
+/// Reads a keyfile, decrypting it if needed, and builds the keypair it describes
+fn load_keyfile(path: PathBuf, label: &str) -> Result<sr25519::Pair, Error> {
+    let raw = fs::read(&path)
+        .map_err(|e| Error::Keyfile(format!("Failed to read {}: {}", path.display(), e)))?;
+
+    let data = if raw.starts_with(NACL_PREFIX) {
+        let password = match std::env::var(PASSWORD_ENV_VAR) {
+            Ok(password) => password,
+            Err(_) => rpassword::prompt_password(format!("Enter password to unlock {}: ", label))
+                .map_err(|e| Error::Keyfile(format!("Failed to read password: {}", e)))?,
+        };
+        info!("Decrypting {}...", label);
+        decrypt_nacl(&raw[NACL_PREFIX.len()..], password.as_bytes())?
+    } else if raw.first() == Some(&b'{') {
+        raw
+    } else {
+        return Err(Error::Keyfile(format!(
+            "{} uses an unsupported encryption format, re-encrypt it with btcli",
+            path.display()
+        )));
+    };
+
+    let keyfile: KeyfileData = serde_json::from_slice(&data)
+        .map_err(|e| Error::Keyfile(format!("Invalid keyfile {}: {}", path.display(), e)))?;
+
+    match (keyfile.secret_seed, keyfile.secret_phrase) {
+        (Some(seed), _) => pair_from_secret(&seed, label),
+        (None, Some(phrase)) => pair_from_secret(&phrase, label),
+        (None, None) => Err(Error::Keyfile(format!(
+            "{} contains neither a secret seed nor a secret phrase",
+            path.display()
+        ))),
+    }
+}
+
+/// Decrypts the body of a `$NACL` keyfile (nonce followed by the sealed box)
+fn decrypt_nacl(encrypted: &[u8], password: &[u8]) -> Result<Vec<u8>, Error> {
+    if encrypted.len() < NONCE_SIZE {
+        return Err(Error::Keyfile("Encrypted keyfile is truncated".into()));
+    }
+
+    let params = Params::new(ARGON2_M_COST, ARGON2_T_COST, 1, Some(32))
+        .map_err(|e| Error::Keyfile(e.to_string()))?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2i, Version::V0x13, params)
+        .hash_password_into(password, NACL_SALT, &mut key)
+        .map_err(|e| Error::Keyfile(e.to_string()))?;
+
+    let (nonce, ciphertext) = encrypted.split_at(NONCE_SIZE);
+    XSalsa20Poly1305::new(Key::from_slice(&key))
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| Error::Keyfile("Wrong password or corrupted keyfile".into()))
+}
+
+fn pair_from_secret(secret: &str, label: &str) -> Result<sr25519::Pair, Error> {
+    sr25519::Pair::from_string(secret, None)
+        .map_err(|_| Error::Keyfile(format!("Invalid {}", label)))
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use subxt::ext::sp_core::crypto::Ss58Codec;
+
+    /// Writes `contents` as the coldkey of a wallet in its own temporary directory
+    fn wallet(name: &str, contents: &[u8]) -> (Wallet, PathBuf) {
+        let root = std::env::temp_dir().join(format!("wallets-{}-{}", name, std::process::id()));
+        fs::create_dir_all(root.join(name)).unwrap();
+        fs::write(root.join(name).join("coldkey"), contents).unwrap();
+        (Wallet::new(name, root.to_str()), root)
+    }
+
+    #[test]
+    fn unencrypted_keyfile_is_loaded() {
+        let keyfile = r#"{
+            "accountId": "0xd43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d",
+            "publicKey": "0xd43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d",
+            "secretPhrase": null,
+            "secretSeed": "0xe5be9a5092b81bca64be81d212e7f2f9eba183bb7a90954f7b76361f6edb5c0a",
+            "ss58Address": "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY"
+        }"#;
+        let (wallet, root) = wallet("plain", keyfile.as_bytes());
+
+        let coldkey = wallet.coldkey();
+        fs::remove_dir_all(root).unwrap();
+        assert_eq!(
+            coldkey.unwrap().public().to_ss58check(),
+            "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY"
+        );
+    }
+
+    #[test]
+    fn keyfile_without_a_secret_is_rejected() {
+        let keyfile = r#"{"secretPhrase": null, "secretSeed": null}"#;
+        let (wallet, root) = wallet("empty", keyfile.as_bytes());
+
+        let coldkey = wallet.coldkey();
+        fs::remove_dir_all(root).unwrap();
+        assert!(matches!(coldkey, Err(Error::Keyfile(_))));
+    }
+
+    #[test]
+    fn ansible_vault_keyfile_is_rejected() {
+        let keyfile = b"$ANSIBLE_VAULT;1.1;AES256\n6433356436626236383466\n";
+        let (wallet, root) = wallet("vault", keyfile);
+
+        let coldkey = wallet.coldkey();
+        fs::remove_dir_all(root).unwrap();
+        match coldkey {
+            Err(Error::Keyfile(message)) => {
+                assert!(message.contains("unsupported encryption format"))
+            }
+            _ => panic!("the ansible-vault keyfile was not rejected"),
+        }
+    }
+}
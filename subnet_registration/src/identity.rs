@@ -0,0 +1,250 @@
+//! Owner hotkey and identity of a new subnet.
+//!
+//! Runtimes differ in how a subnet gets its owner hotkey and identity: newer ones have
+//! `register_network_with_identity`, some take a `hotkey` in `register_network`, and older ones
+//! use the coldkey as the owner hotkey and can only set the identity afterwards with
+//! `set_subnet_identity`, if at all. These calls are looked up in the node's metadata and built
+//! dynamically, since `metadata.scale` may predate them.
+
+use clap::Args;
+use log::info;
+use subxt::dynamic::Value;
+use subxt::ext::scale_value::Composite;
+use subxt::ext::sp_core::sr25519;
+use subxt::tx::{DynamicPayload, PairSigner, Payload};
+use subxt::utils::AccountId32;
+use subxt::{Metadata, OnlineClient, SubstrateConfig};
+
+const PALLET: &str = "SubtensorModule";
+
+/// Fields of the subnet identity type in the runtime versions we know of, newest first
+const IDENTITY_FIELDS: [&[&str]; 3] = [
+    &[
+        "subnet_name",
+        "github_repo",
+        "subnet_contact",
+        "subnet_url",
+        "discord",
+        "description",
+        "logo_url",
+        "additional",
+    ],
+    &[
+        "subnet_name",
+        "github_repo",
+        "subnet_contact",
+        "subnet_url",
+        "discord",
+        "description",
+        "additional",
+    ],
+    &["subnet_name", "github_repo", "subnet_contact"],
+];
+
+/// Identity of the new subnet, unset fields are left empty
+#[derive(Args, Debug, Clone, Default)]
+pub struct IdentityParams {
+    /// Name of the subnet
+    #[clap(long)]
+    pub subnet_name: Option<String>,
+
+    /// GitHub repository of the subnet
+    #[clap(long)]
+    pub github_repo: Option<String>,
+
+    /// Contact of the subnet owner
+    #[clap(long)]
+    pub subnet_contact: Option<String>,
+
+    /// Website of the subnet
+    #[clap(long)]
+    pub subnet_url: Option<String>,
+
+    /// Description of the subnet
+    #[clap(long)]
+    pub subnet_description: Option<String>,
+}
+
+impl IdentityParams {
+    /// Returns `true` if no identity field is set
+    pub fn is_empty(&self) -> bool {
+        self.subnet_name.is_none()
+            && self.github_repo.is_none()
+            && self.subnet_contact.is_none()
+            && self.subnet_url.is_none()
+            && self.subnet_description.is_none()
+    }
+
+    /// Returns the value of an identity field by its name in the runtime, empty if it is unset
+    /// or has no matching parameter
+    fn field(&self, name: &str) -> Value {
+        let value = match name {
+            "subnet_name" => &self.subnet_name,
+            "github_repo" => &self.github_repo,
+            "subnet_contact" => &self.subnet_contact,
+            "subnet_url" => &self.subnet_url,
+            "description" => &self.subnet_description,
+            _ => &None,
+        };
+        Value::from_bytes(value.as_deref().unwrap_or_default())
+    }
+}
+
+/// The registration call chosen for the runtime
+pub struct RegistrationPlan {
+    /// Name of the `SubtensorModule` call
+    pub call_name: &'static str,
+    /// The call to submit
+    pub call: DynamicPayload,
+    /// Whether the identity still has to be set with `set_subnet_identity` once registered
+    pub set_identity_after: bool,
+}
+
+/// Chooses the registration call that carries the owner hotkey and identity
+///
+/// The identity goes into `register_network_with_identity` when the runtime has it. Otherwise
+/// it is set with `set_subnet_identity` after registering.
+///
+/// # Arguments
+///
+/// * `metadata` - The node's metadata
+/// * `coldkey` - The coldkey registering the subnet, the owner hotkey unless `hotkey` is set
+/// * `hotkey` - The owner hotkey
+/// * `identity` - The identity of the subnet
+///
+/// # Returns
+///
+/// A `Result` containing the `RegistrationPlan`, or an `Err` if the runtime cannot register
+/// the subnet with the hotkey or set its identity
+pub fn plan_registration(
+    metadata: &Metadata,
+    coldkey: &AccountId32,
+    hotkey: Option<&AccountId32>,
+    identity: &IdentityParams,
+) -> Result<RegistrationPlan, Box<dyn std::error::Error>> {
+    let pallet = metadata
+        .pallet_by_name(PALLET)
+        .ok_or("SubtensorModule not found in the metadata")?;
+    let has_call = |name: &str| pallet.call_variant_by_name(name).is_some();
+    let takes_hotkey = |name: &str| {
+        pallet.call_variant_by_name(name).is_some_and(|call| {
+            call.fields
+                .iter()
+                .any(|field| field.name.as_deref() == Some("hotkey"))
+        })
+    };
+    let owner = Value::from_bytes(hotkey.unwrap_or(coldkey).0);
+
+    if has_call("register_network_with_identity") && !identity.is_empty() {
+        for fields in IDENTITY_FIELDS {
+            let value =
+                Value::named_composite(fields.iter().map(|&name| (name, identity.field(name))));
+            let call = subxt::dynamic::tx(
+                PALLET,
+                "register_network_with_identity",
+                Composite::named([
+                    ("hotkey", owner.clone()),
+                    ("identity", Value::unnamed_variant("Some", [value])),
+                ]),
+            );
+            // Only the field set of this runtime's identity type encodes
+            if call.encode_call_data(metadata).is_ok() {
+                return Ok(RegistrationPlan {
+                    call_name: "register_network_with_identity",
+                    call,
+                    set_identity_after: false,
+                });
+            }
+        }
+        return Err("Unknown subnet identity type in register_network_with_identity".into());
+    }
+
+    let set_identity_after = !identity.is_empty();
+    if set_identity_after && !has_call("set_subnet_identity") {
+        return Err("The runtime has no call to set a subnet identity".into());
+    }
+
+    if takes_hotkey("register_network") {
+        return Ok(RegistrationPlan {
+            call_name: "register_network",
+            call: subxt::dynamic::tx(
+                PALLET,
+                "register_network",
+                Composite::named([("hotkey", owner)]),
+            ),
+            set_identity_after,
+        });
+    }
+    if hotkey.is_some_and(|hotkey| hotkey != coldkey) {
+        if !has_call("register_network_with_identity") {
+            return Err("The runtime cannot register a subnet with an owner hotkey".into());
+        }
+        return Ok(RegistrationPlan {
+            call_name: "register_network_with_identity",
+            call: subxt::dynamic::tx(
+                PALLET,
+                "register_network_with_identity",
+                Composite::named([
+                    ("hotkey", owner),
+                    ("identity", Value::unnamed_variant("None", [])),
+                ]),
+            ),
+            set_identity_after,
+        });
+    }
+
+    Ok(RegistrationPlan {
+        call_name: "register_network",
+        call: subxt::dynamic::tx(PALLET, "register_network", Composite::unnamed(Vec::new())),
+        set_identity_after,
+    })
+}
+
+/// Sets the identity of `netuid` with `set_subnet_identity`
+///
+/// The call's arguments are read from the node's metadata, so fields this tool has no
+/// parameter for are set empty.
+///
+/// # Arguments
+///
+/// * `client` - A reference to the blockchain client
+/// * `signer` - The subnet owner's coldkey
+/// * `netuid` - The subnet to set the identity of
+/// * `identity` - The identity of the subnet
+///
+/// # Returns
+///
+/// A `Result` which is `Ok` once the identity is set in a finalized block, or an `Err` if the
+/// call is missing or fails
+pub async fn set_identity(
+    client: &OnlineClient<SubstrateConfig>,
+    signer: &PairSigner<SubstrateConfig, sr25519::Pair>,
+    netuid: u16,
+    identity: &IdentityParams,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let metadata = client.metadata();
+    let call = metadata
+        .pallet_by_name(PALLET)
+        .and_then(|pallet| pallet.call_variant_by_name("set_subnet_identity"))
+        .ok_or("set_subnet_identity not found in the metadata")?;
+    let fields = call.fields.iter().map(|field| {
+        let name = field.name.clone().unwrap_or_default();
+        let value = if name == "netuid" {
+            Value::u128(netuid.into())
+        } else {
+            identity.field(&name)
+        };
+        (name, value)
+    });
+    let call = subxt::dynamic::tx(PALLET, "set_subnet_identity", Composite::named(fields));
+
+    client
+        .tx()
+        .sign_and_submit_then_watch_default(&call, signer)
+        .await?
+        .wait_for_finalized_success()
+        .await?;
+    info!("Set the identity of subnet {}", netuid);
+
+    Ok(())
+}
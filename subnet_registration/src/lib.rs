@@ -1,42 +1,203 @@
 use clap::Parser;
-use log::{error, info};
-use serde::Deserialize;
+use log::{error, info, warn};
+use shared::errors::{DispatchErrorKind, ErrorAction, ErrorPolicyParams};
 use shared::estimate_block_time;
+use shared::resilient::{is_connection_error, ResilientClient};
+use shared::tx::{dry_run, tao_to_rao, TipParams};
+use shared::wallet::load_coldkey;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
+use subxt::backend::legacy::LegacyRpcMethods;
 use subxt::backend::rpc::RpcClient;
-use subxt::ext::sp_core::{sr25519, Pair};
+use subxt::ext::sp_core::sr25519;
 use subxt::rpc_params;
+use subxt::tx::PairSigner;
+use subxt::utils::AccountId32;
 use subxt::{OnlineClient, SubstrateConfig};
 mod errors;
+pub mod identity;
+pub mod outcome;
+pub mod pending;
+pub mod spec;
+mod submission;
 use crate::errors::Error;
+use crate::identity::{plan_registration, set_identity, IdentityParams, RegistrationPlan};
+use crate::outcome::SubnetRegistrationOutcome;
+use crate::pending::summarise;
+use crate::spec::{apply_spec, SubnetSpec};
+use crate::submission::{check_success, Resolution, Submission};
 
 #[subxt::subxt(runtime_metadata_path = "metadata.scale")]
 pub mod runtime {}
 
-#[derive(Parser, Deserialize, Debug)]
+#[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 pub struct SubnetRegistrationParams {
+    /// Mnemonic or seed of the coldkey, takes precedence over the wallet
     #[clap(long)]
-    pub coldkey: String,
+    pub coldkey: Option<String>,
+
+    /// Name of the wallet to load the coldkey from
+    #[clap(long)]
+    pub wallet_name: Option<String>,
+
+    /// Directory containing the wallets, defaults to ~/.bittensor/wallets
+    #[clap(long)]
+    pub wallet_path: Option<String>,
+
+    /// SS58 address of the subnet owner hotkey, defaults to the coldkey
+    #[clap(long)]
+    pub owner_hotkey: Option<String>,
+
+    #[clap(flatten)]
+    pub identity: IdentityParams,
 
     #[clap(long, default_value = "ws://127.0.0.1:9944")]
     pub chain_endpoint: String,
+
+    /// Highest network lock cost to submit `register_network` at, in TAO. Required to register
+    /// a subnet, not with `--configure-netuid`
+    #[clap(long)]
+    pub max_lock_cost_tao: Option<String>,
+
+    /// Spec file of hyperparameters to apply once the subnet is registered
+    #[clap(long)]
+    pub spec: Option<String>,
+
+    /// Apply the spec to this existing subnet instead of registering a new one
+    #[clap(long, requires = "spec")]
+    pub configure_netuid: Option<u16>,
+
+    #[clap(flatten)]
+    pub tip: TipParams,
+
+    #[clap(flatten)]
+    pub on_error: ErrorPolicyParams,
+
+    /// Sign and validate the registration extrinsic without broadcasting it
+    #[clap(long)]
+    pub dry_run: bool,
 }
 
+/// Registers a new subnet, retrying until it succeeds or a rejection stops it
+///
+/// # Arguments
+///
+/// * `params` - A reference to `SubnetRegistrationParams` containing registration details
+///
+/// # Returns
+///
+/// A `Result` containing the `SubnetRegistrationOutcome`, `None` for a dry run, if a rejected
+/// attempt stopped the loop or if only a spec was applied, or an `Err` containing the error
+/// message
 pub async fn register_subnet(
     params: &SubnetRegistrationParams,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<Option<SubnetRegistrationOutcome>, Box<dyn std::error::Error>> {
     let chain_endpoint = params.chain_endpoint.clone();
 
-    let client = Arc::new(OnlineClient::<SubstrateConfig>::from_url(&params.chain_endpoint).await?);
+    let connection = ResilientClient::connect(&params.chain_endpoint).await?;
+    let rpc = connection.rpc();
+    let client = Arc::new(connection.client());
+
+    let coldkey: sr25519::Pair = load_coldkey(
+        params.coldkey.as_deref(),
+        params.wallet_name.as_deref(),
+        params.wallet_path.as_deref(),
+    )?;
+    let signer = PairSigner::new(coldkey);
+    // A bad spec is reported before any TAO is locked
+    let spec = params.spec.as_deref().map(SubnetSpec::load).transpose()?;
 
-    let coldkey: sr25519::Pair =
-        Pair::from_string(&params.coldkey, None).map_err(|_| "Invalid coldkey")?;
-    let signer = subxt::tx::PairSigner::new(coldkey);
+    if let Some(netuid) = params.configure_netuid {
+        let spec = spec.ok_or("configure_netuid requires a spec")?;
+        apply_spec(&client, &signer, netuid, &spec, params.dry_run).await?;
+        return Ok(None);
+    }
+
+    let owner_hotkey = params
+        .owner_hotkey
+        .as_deref()
+        .map(|hotkey| {
+            AccountId32::from_str(hotkey)
+                .map_err(|e| format!("Invalid owner hotkey address {}: {}", hotkey, e))
+        })
+        .transpose()?;
+    // The calls taking a hotkey or an identity are looked up in the node's metadata
+    let plan = plan_registration(
+        &client.metadata(),
+        signer.account_id(),
+        owner_hotkey.as_ref(),
+        &params.identity,
+    )?;
+    let call = &plan.call;
+    info!(
+        "Registering with {}{}",
+        plan.call_name,
+        if plan.set_identity_after {
+            ", then set_subnet_identity"
+        } else {
+            ""
+        }
+    );
+    let tip_policy = params.tip.policy()?;
+    let max_lock_cost_rao = params
+        .max_lock_cost_tao
+        .as_deref()
+        .map(tao_to_rao)
+        .transpose()?;
 
+    if params.dry_run {
+        info!("Dry run: the registration extrinsic will not be broadcast.");
+        let lock_cost_rao = get_lock_cost(&client).await?;
+        let first_block = get_first_registration_block(&client).await?;
+        let current_block = u64::from(client.blocks().at_latest().await?.number());
+        let report = dry_run(&client, &rpc, call, &signer, tip_policy.tip_rao(0)).await?;
+
+        println!("Dry run of {}:", plan.call_name);
+        println!("Coldkey: {}", signer.account_id());
+        println!(
+            "Owner hotkey: {}",
+            owner_hotkey.as_ref().unwrap_or(signer.account_id())
+        );
+        if plan.set_identity_after {
+            println!("The identity is set with set_subnet_identity once registered");
+        }
+        match (&params.max_lock_cost_tao, max_lock_cost_rao) {
+            (Some(max_lock_cost_tao), Some(max_lock_cost_rao)) => println!(
+                "Lock cost: {:.9} TAO (max {} TAO{})",
+                lock_cost_rao as f64 / 1e9,
+                max_lock_cost_tao,
+                if lock_cost_rao > max_lock_cost_rao {
+                    ", exceeded"
+                } else {
+                    ""
+                }
+            ),
+            _ => println!(
+                "Lock cost: {:.9} TAO (max_lock_cost_tao is not set, registering requires it)",
+                lock_cost_rao as f64 / 1e9
+            ),
+        }
+        if current_block + 1 < first_block {
+            println!(
+                "Rate limited until block {} (current block {})",
+                first_block, current_block
+            );
+        }
+        println!("{}", report);
+        return Ok(None);
+    }
+
+    let (Some(max_lock_cost_tao), Some(max_lock_cost_rao)) =
+        (params.max_lock_cost_tao.as_deref(), max_lock_cost_rao)
+    else {
+        return Err("max_lock_cost_tao is required to register a subnet".into());
+    };
+
+    let own = signer.account_id().clone();
     tokio::spawn(async move {
-        if let Err(e) = monitor_pending_extrinsics(&chain_endpoint).await {
+        if let Err(e) = monitor_pending_extrinsics(&chain_endpoint, own).await {
             error!("Error in monitor_pending_extrinsics: {:?}", e);
         }
     });
@@ -44,44 +205,415 @@ pub async fn register_subnet(
     let estimated_block_time = estimate_block_time(&client).await?;
     info!("Estimated block time: {:?}", estimated_block_time);
 
-    let call = runtime::tx().subtensor_module().register_network();
-
+    let mut failed_attempts: u32 = 0;
+    let mut backoff = params.on_error.backoff();
+    // A registration that may still land after its outcome was lost with the connection
+    let mut submitted: Option<Submission> = None;
     loop {
-        match client
-            .tx()
-            .sign_and_submit_then_watch(&call, &signer, Default::default())
-            .await
-        {
-            Ok(result) => match result.wait_for_finalized_success().await {
-                Ok(events) => {
-                    info!("Subnet registration successful. Events: {:?}", events);
-                    break;
+        // A dropped connection is rebuilt instead of failing every following attempt
+        let client = connection.client();
+        let rpc = connection.rpc();
+
+        let landed = match submitted.as_mut() {
+            // Signing a new registration while this one can land would pay the lock twice
+            Some(submission) => match submission.resolve(&client, &rpc, &signer).await {
+                Ok(Resolution::Included(block_hash, events)) => check_success(&client, events)
+                    .map(|events| (block_hash, events))
+                    .map_err(Into::into),
+                Ok(Resolution::Pending) => {
+                    info!(
+                        "Waiting for extrinsic {:?} to land or expire",
+                        submission.extrinsic_hash
+                    );
+                    tokio::time::sleep(estimated_block_time).await;
+                    continue;
+                }
+                Ok(Resolution::Gone) => {
+                    warn!(
+                        "Extrinsic {:?} was not included, registering again",
+                        submission.extrinsic_hash
+                    );
+                    submitted = None;
+                    continue;
                 }
-                Err(e) => error!("Subnet registration failed: {:?}", e),
+                Err(e) if is_connection_error(e.as_ref()) => Err(e),
+                Err(e) => return Err(e),
             },
-            Err(e) => error!("Failed to submit extrinsic: {:?}", e),
+            None => {
+                // Every attempt before the network rate limit has passed would fail
+                if let Err(e) = wait_for_rate_limit(&client, &rpc, estimated_block_time).await {
+                    if is_connection_error(e.as_ref()) {
+                        error!("Lost the connection to the chain: {:?}", e);
+                        connection.reconnect().await;
+                        continue;
+                    }
+                    return Err(e);
+                }
+
+                // The lock is taken from the coldkey at whatever the cost is when the extrinsic
+                // lands
+                let lock_cost_rao = match get_lock_cost(&client).await {
+                    Ok(lock_cost_rao) => lock_cost_rao,
+                    Err(e) if is_connection_error(e.as_ref()) => {
+                        error!("Lost the connection to the chain: {:?}", e);
+                        connection.reconnect().await;
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                };
+                if lock_cost_rao > max_lock_cost_rao {
+                    warn!(
+                        "Lock cost ({:.9} TAO) exceeds the maximum ({} TAO). Waiting for it to decay.",
+                        lock_cost_rao as f64 / 1e9,
+                        max_lock_cost_tao
+                    );
+                    tokio::time::sleep(estimated_block_time).await;
+                    continue;
+                }
+
+                let tip_rao = tip_policy.tip_rao(failed_attempts);
+                info!(
+                    "Submitting {} at a {:.9} TAO lock cost with a {:.9} TAO tip",
+                    plan.call_name,
+                    lock_cost_rao as f64 / 1e9,
+                    tip_rao as f64 / 1e9
+                );
+                let extrinsic = match Submission::sign(&client, call, &signer, tip_rao).await {
+                    Ok((submission, extrinsic)) => {
+                        submitted = Some(submission);
+                        extrinsic
+                    }
+                    Err(e) if is_connection_error(e.as_ref()) => {
+                        error!("Lost the connection to the chain: {:?}", e);
+                        connection.reconnect().await;
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                };
+                async {
+                    let in_block = extrinsic
+                        .submit_and_watch()
+                        .await?
+                        .wait_for_finalized()
+                        .await?;
+                    let events = in_block.wait_for_success().await?;
+                    Ok::<_, subxt::Error>((in_block.block_hash(), events))
+                }
+                .await
+                .map_err(Into::into)
+            }
+        };
+
+        let failure: Box<dyn std::error::Error> = match landed {
+            Ok((block_hash, events)) => {
+                match SubnetRegistrationOutcome::from_events(
+                    &client,
+                    block_hash,
+                    &events,
+                    signer.account_id(),
+                )
+                .await
+                {
+                    Ok(outcome) => {
+                        return Ok(Some(
+                            finish_registration(&client, &signer, params, &plan, &spec, outcome)
+                                .await,
+                        ))
+                    }
+                    // The subnet is registered, its events are read again after reconnecting
+                    Err(e) if is_connection_error(e.as_ref()) => e,
+                    Err(e) => return Err(e),
+                }
+            }
+            Err(e) => e,
+        };
+
+        if is_connection_error(failure.as_ref()) {
+            error!("Lost the connection to the chain: {:?}", failure);
+            connection.reconnect().await;
+        } else {
+            // The extrinsic was rejected or failed, so it cannot take the lock any more
+            submitted = None;
+            let kind = DispatchErrorKind::classify(failure.as_ref());
+            let action = params.on_error.action(kind);
+            error!(
+                "Subnet registration failed ({}, {}): {}",
+                kind, action, failure
+            );
+            failed_attempts += 1;
+            match action {
+                ErrorAction::Stop => {
+                    warn!("Stopping after a rejected subnet registration ({}).", kind);
+                    return Ok(None);
+                }
+                ErrorAction::Fail => {
+                    return Err(shared::errors::Error::Dispatch(kind, failure.to_string()).into());
+                }
+                ErrorAction::Backoff => {
+                    let delay = backoff.next_delay();
+                    warn!(
+                        "Backing off for {:?} after a rejected subnet registration ({}).",
+                        delay, kind
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                ErrorAction::Retry => backoff = params.on_error.backoff(),
+            }
         }
         tokio::time::sleep(estimated_block_time).await;
     }
+}
+
+/// Logs a successful registration and sets up the new subnet
+///
+/// The subnet exists either way, so failures to set the identity or apply the spec are only
+/// logged and the spec can be applied again with `--configure-netuid`.
+///
+/// # Arguments
+///
+/// * `client` - A reference to the blockchain client
+/// * `signer` - The coldkey owning the subnet
+/// * `params` - The registration parameters, holding the identity
+/// * `plan` - The registration plan, telling whether the identity is still to be set
+/// * `spec` - The hyperparameter spec to apply, if any
+/// * `outcome` - The outcome of the registration
+///
+/// # Returns
+///
+/// The `outcome`
+async fn finish_registration(
+    client: &OnlineClient<SubstrateConfig>,
+    signer: &PairSigner<SubstrateConfig, sr25519::Pair>,
+    params: &SubnetRegistrationParams,
+    plan: &RegistrationPlan,
+    spec: &Option<SubnetSpec>,
+    outcome: SubnetRegistrationOutcome,
+) -> SubnetRegistrationOutcome {
+    info!(
+        "Subnet registration successful: netuid {} at block {}, {:.9} TAO locked",
+        outcome.netuid,
+        outcome.block_number,
+        outcome.lock_rao as f64 / 1e9
+    );
+    if plan.set_identity_after {
+        if let Err(e) = set_identity(client, signer, outcome.netuid, &params.identity).await {
+            error!(
+                "Failed to set the identity of subnet {}: {}",
+                outcome.netuid, e
+            );
+        }
+    }
+    if let Some(spec) = spec {
+        if let Err(e) = apply_spec(client, signer, outcome.netuid, spec, false).await {
+            error!(
+                "Failed to apply the spec to subnet {}: {}",
+                outcome.netuid, e
+            );
+        }
+    }
+
+    outcome
+}
+
+/// Retrieves the current network registration (lock) cost
+///
+/// # Arguments
+///
+/// * `client` - A reference to the blockchain client
+///
+/// # Returns
+///
+/// A `Result` containing the lock cost in RAO, or an `Err` if the runtime API call fails
+pub async fn get_lock_cost(
+    client: &OnlineClient<SubstrateConfig>,
+) -> Result<u64, Box<dyn std::error::Error>> {
+    let call = runtime::apis()
+        .subnet_registration_runtime_api()
+        .get_network_registration_cost();
+    let lock_cost = client.runtime_api().at_latest().await?.call(call).await?;
+
+    Ok(lock_cost)
+}
+
+/// Retrieves the first block in which `register_network` passes the network rate limit
+///
+/// # Arguments
+///
+/// * `client` - A reference to the blockchain client
+///
+/// # Returns
+///
+/// A `Result` containing the block number `NetworkLastRegistered + NetworkRateLimit`, or an
+/// `Err` if the storage cannot be read
+pub async fn get_first_registration_block(
+    client: &OnlineClient<SubstrateConfig>,
+) -> Result<u64, Box<dyn std::error::Error>> {
+    let storage = client.storage().at_latest().await?;
+    let rate_limit = storage
+        .fetch_or_default(&runtime::storage().subtensor_module().network_rate_limit())
+        .await?;
+    let last_registered = storage
+        .fetch_or_default(
+            &runtime::storage()
+                .subtensor_module()
+                .network_last_registered(),
+        )
+        .await?;
+
+    Ok(last_registered.saturating_add(rate_limit))
+}
+
+/// Predicts the netuid a subnet registered now gets
+///
+/// Below the subnet limit a new subnet takes the lowest free netuid above the root network.
+/// At the limit it replaces a pruned subnet, which is not predicted.
+///
+/// # Arguments
+///
+/// * `client` - A reference to the blockchain client
+///
+/// # Returns
+///
+/// A `Result` containing the netuid, `None` if the subnet limit is reached, or an `Err` if the
+/// storage cannot be read
+pub async fn get_next_netuid(
+    client: &OnlineClient<SubstrateConfig>,
+) -> Result<Option<u16>, Box<dyn std::error::Error>> {
+    let storage = client.storage().at_latest().await?;
+    let subtensor = runtime::storage().subtensor_module();
+    let total_networks = storage
+        .fetch_or_default(&subtensor.total_networks())
+        .await?;
+    let subnet_limit = storage.fetch_or_default(&subtensor.subnet_limit()).await?;
+    // The root network does not count towards the limit
+    if total_networks.saturating_sub(1) >= subnet_limit {
+        return Ok(None);
+    }
+
+    let mut netuid: u16 = 1;
+    while storage
+        .fetch_or_default(&subtensor.networks_added(netuid))
+        .await?
+    {
+        netuid = netuid.checked_add(1).ok_or("No free netuid")?;
+    }
+
+    Ok(Some(netuid))
+}
+
+/// Waits until an extrinsic submitted now would land in a block that passes the rate limit
+///
+/// The current best block is checked first, so no block is waited for once the limit has
+/// passed. Otherwise the wait follows best blocks and returns as soon as the best block is the
+/// one right before the first legal block, so the extrinsic can be included in that block.
+///
+/// # Arguments
+///
+/// * `client` - A reference to the blockchain client
+/// * `rpc` - The RPC client, used to read the current best block
+/// * `block_time` - The estimated block time, used for the ETA
+///
+/// # Returns
+///
+/// A `Result` which is `Ok` once registration is legal in the next block, or an `Err` if the
+/// chain cannot be followed
+async fn wait_for_rate_limit(
+    client: &OnlineClient<SubstrateConfig>,
+    rpc: &RpcClient,
+    block_time: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let first_block = get_first_registration_block(client).await?;
+    let best = LegacyRpcMethods::<SubstrateConfig>::new(rpc.clone())
+        .chain_get_header(None)
+        .await?
+        .ok_or(shared::errors::Error::BlockHeaderNotFound)?;
+    if u64::from(best.number) + 1 >= first_block {
+        return Ok(());
+    }
+
+    let mut blocks = client.blocks().subscribe_best().await?;
+    let mut announced = false;
+    loop {
+        let block = blocks
+            .next()
+            .await
+            .ok_or(shared::errors::Error::Disconnected(
+                "Block subscription ended".to_string(),
+            ))??;
+        let next_block = u64::from(block.number()) + 1;
+        if next_block >= first_block {
+            return Ok(());
+        }
 
-    Ok(())
+        if !announced {
+            let remaining = first_block - next_block;
+            let eta = chrono::Utc::now()
+                + chrono::Duration::from_std(block_time * (remaining as u32 + 1))?;
+            info!(
+                "Network registration is rate limited until block {} ({} blocks, ETA {})",
+                first_block,
+                remaining + 1,
+                eta.with_timezone(&chrono_tz::US::Eastern)
+                    .format("%Y-%m-%d %H:%M:%S %Z%z")
+            );
+            announced = true;
+        }
+    }
 }
 
-async fn monitor_pending_extrinsics(chain_endpoint: &str) -> Result<(), Error> {
-    let rpc = RpcClient::from_url(chain_endpoint)
+/// Logs a summary of the transaction pool every 5 seconds
+///
+/// Every pending extrinsic is decoded against the node's metadata. The number of pending
+/// extrinsics per call is logged, followed by a warning for each subnet registration, or
+/// `burned_register` on the netuid our subnet will get, by another account with its signer,
+/// hotkey, tip and nonce.
+///
+/// # Arguments
+///
+/// * `chain_endpoint` - The node to read the transaction pool from
+/// * `own` - Our coldkey, whose registration is not reported as competing
+///
+/// # Returns
+///
+/// Only returns, with an `Err`, if the first connection fails
+async fn monitor_pending_extrinsics(chain_endpoint: &str, own: AccountId32) -> Result<(), Error> {
+    let connection = ResilientClient::connect(chain_endpoint)
         .await
         .map_err(|e| Error::ConnectionError(e.to_string()))?;
     let mut interval = tokio::time::interval(Duration::from_secs(5));
     loop {
         interval.tick().await;
-        match rpc.request("author_pendingExtrinsics", rpc_params![]).await {
-            Ok(pending_extrinsics) => {
-                let extrinsics: Vec<String> = pending_extrinsics;
-                info!("Number of pending extrinsics: {}", extrinsics.len());
-                for (index, extrinsic) in extrinsics.iter().enumerate() {
-                    info!("Extrinsic {}: {}", index, extrinsic);
+        match connection
+            .rpc()
+            .request::<Vec<String>>("author_pendingExtrinsics", rpc_params![])
+            .await
+        {
+            Ok(extrinsics) => {
+                let client = connection.client();
+                let target_netuid = match get_next_netuid(&client).await {
+                    Ok(netuid) => netuid,
+                    Err(e) => {
+                        warn!("Failed to predict the netuid of the new subnet: {:?}", e);
+                        None
+                    }
+                };
+                match summarise(&extrinsics, client.metadata(), &own, target_netuid) {
+                    Ok(summary) => {
+                        info!("{}", summary);
+                        for extrinsic in &summary.competing {
+                            warn!("Competing registration pending: {}", extrinsic);
+                        }
+                    }
+                    Err(e) => error!("Failed to decode pending extrinsics: {:?}", e),
                 }
             }
+            Err(e) if is_connection_error(&e) => {
+                error!(
+                    "Lost the connection while fetching pending extrinsics: {:?}",
+                    e
+                );
+                connection.reconnect().await;
+            }
             Err(e) => error!("Failed to fetch pending extrinsics: {:?}", e),
         }
     }
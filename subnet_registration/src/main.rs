@@ -1,4 +1,3 @@
-use env_logger;
 use shared::parse_config;
 use subnet_registration::{register_subnet, SubnetRegistrationParams};
 
@@ -6,5 +5,9 @@ use subnet_registration::{register_subnet, SubnetRegistrationParams};
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init();
     let params: SubnetRegistrationParams = parse_config()?;
-    register_subnet(&params).await
+    // Logs go to stderr, so stdout only carries the outcome for automation
+    if let Some(outcome) = register_subnet(&params).await? {
+        println!("{}", serde_json::to_string_pretty(&outcome)?);
+    }
+    Ok(())
 }
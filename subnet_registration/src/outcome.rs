@@ -0,0 +1,72 @@
+//! Decoding of the subnet registration events and the summary returned to the caller.
+
+use crate::runtime::subtensor_module::events::NetworkAdded;
+use serde::Serialize;
+use shared::budget::Spend;
+use subxt::blocks::ExtrinsicEvents;
+use subxt::utils::{AccountId32, H256};
+use subxt::{OnlineClient, SubstrateConfig};
+
+/// Summary of a successful subnet registration, written out as JSON
+#[derive(Serialize, Debug, Clone)]
+pub struct SubnetRegistrationOutcome {
+    /// The netuid of the new subnet
+    pub netuid: u16,
+    /// The coldkey owning the subnet
+    pub coldkey: AccountId32,
+    /// TAO locked for the subnet, in RAO
+    pub lock_rao: u64,
+    /// Transaction fee without the tip, in RAO
+    pub fee_rao: u64,
+    /// Tip paid, in RAO
+    pub tip_rao: u64,
+    /// Number of the block that included the registration
+    pub block_number: u64,
+    /// Hash of the block that included the registration
+    pub block_hash: H256,
+    /// Hash of the `register_network` extrinsic
+    pub extrinsic_hash: H256,
+}
+
+impl SubnetRegistrationOutcome {
+    /// Builds the outcome from the events of a finalized `register_network` extrinsic
+    ///
+    /// The netuid comes from `SubtensorModule::NetworkAdded`. The runtime emits no event for
+    /// the lock itself: it is withdrawn from the coldkey, so it is what the coldkey's
+    /// `Balances::Withdraw` events took beyond the fee and the tip.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - A reference to the blockchain client, used to look up the block number
+    /// * `block_hash` - Hash of the finalized block that included the extrinsic
+    /// * `events` - The events of the finalized extrinsic
+    /// * `coldkey` - The coldkey that paid for the registration
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `SubnetRegistrationOutcome`, or an `Err` if `NetworkAdded` is
+    /// missing or an event cannot be decoded
+    pub async fn from_events(
+        client: &OnlineClient<SubstrateConfig>,
+        block_hash: H256,
+        events: &ExtrinsicEvents<SubstrateConfig>,
+        coldkey: &AccountId32,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let network_added = events
+            .find_first::<NetworkAdded>()?
+            .ok_or("NetworkAdded event not found")?;
+        let spend = Spend::from_events(events, coldkey)?;
+        let block_number = client.blocks().at(block_hash).await?.number();
+
+        Ok(Self {
+            netuid: network_added.0,
+            coldkey: coldkey.clone(),
+            lock_rao: spend.burn_rao,
+            fee_rao: spend.fee_rao,
+            tip_rao: spend.tip_rao,
+            block_number: block_number.into(),
+            block_hash,
+            extrinsic_hash: events.extrinsic_hash(),
+        })
+    }
+}
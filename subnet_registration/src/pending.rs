@@ -0,0 +1,351 @@
+//! Decoding of the transaction pool's pending extrinsics.
+//!
+//! Each extrinsic returned by `author_pendingExtrinsics` is decoded against the node's metadata
+//! into its signer, call, key arguments, tip and nonce, so competing subnet registrations, and
+//! hotkey registrations on the netuid our subnet will get, can be spotted before they land.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use subxt::ext::codec::Decode;
+use subxt::ext::scale_value::{Composite, Primitive, Value, ValueDef};
+use subxt::ext::sp_core::bytes::from_hex;
+use subxt::utils::{AccountId32, MultiAddress};
+use subxt::{Metadata, SubstrateConfig};
+use subxt_core::blocks::ExtrinsicDetails;
+
+/// Calls that compete with our `register_network` for the lock cost and the network rate limit
+const REGISTRATION_CALLS: [&str; 2] = ["register_network", "register_network_with_identity"];
+
+/// Call that registers a hotkey on a subnet, competing when it targets the netuid our subnet
+/// will get
+const HOTKEY_REGISTRATION_CALL: &str = "burned_register";
+
+/// A decoded pending extrinsic
+#[derive(Debug, Clone)]
+pub struct PendingExtrinsic {
+    /// The signer, `None` for an unsigned extrinsic
+    pub signer: Option<AccountId32>,
+    pub pallet: String,
+    pub call: String,
+    /// The `netuid` argument, if the call has one
+    pub netuid: Option<u16>,
+    /// The `hotkey` argument, if the call has one
+    pub hotkey: Option<AccountId32>,
+    /// Tip in RAO, if the extrinsic carries one
+    pub tip_rao: Option<u128>,
+    pub nonce: Option<u64>,
+}
+
+impl PendingExtrinsic {
+    /// Returns `true` if this is a subnet registration, or a `burned_register` on
+    /// `target_netuid`, by someone other than `own`
+    pub fn is_competing(&self, own: &AccountId32, target_netuid: Option<u16>) -> bool {
+        let targets_us = REGISTRATION_CALLS.contains(&self.call.as_str())
+            || (self.call == HOTKEY_REGISTRATION_CALL
+                && target_netuid.is_some()
+                && self.netuid == target_netuid);
+        self.pallet == "SubtensorModule" && targets_us && self.signer.as_ref() != Some(own)
+    }
+}
+
+impl fmt::Display for PendingExtrinsic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}::{}", self.pallet, self.call)?;
+        match &self.signer {
+            Some(signer) => write!(f, " from {}", signer)?,
+            None => write!(f, " (unsigned)")?,
+        }
+        if let Some(netuid) = self.netuid {
+            write!(f, ", netuid {}", netuid)?;
+        }
+        if let Some(hotkey) = &self.hotkey {
+            write!(f, ", hotkey {}", hotkey)?;
+        }
+        if let Some(tip_rao) = self.tip_rao {
+            write!(f, ", tip {:.9} TAO", tip_rao as f64 / 1e9)?;
+        }
+        if let Some(nonce) = self.nonce {
+            write!(f, ", nonce {}", nonce)?;
+        }
+        Ok(())
+    }
+}
+
+/// Summary of the transaction pool
+#[derive(Debug, Default)]
+pub struct PendingSummary {
+    /// Number of pending extrinsics
+    pub total: usize,
+    /// Number of pending extrinsics per `pallet::call`
+    pub calls: BTreeMap<String, usize>,
+    /// Number of extrinsics that are not valid hex or could not be decoded
+    pub undecodable: usize,
+    /// Subnet registrations and registrations on the target netuid by other accounts
+    pub competing: Vec<PendingExtrinsic>,
+}
+
+impl fmt::Display for PendingSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} pending extrinsics", self.total)?;
+        let calls: Vec<String> = self
+            .calls
+            .iter()
+            .map(|(call, count)| format!("{} {}", call, count))
+            .collect();
+        if !calls.is_empty() {
+            write!(f, ": {}", calls.join(", "))?;
+        }
+        if self.undecodable > 0 {
+            write!(f, " ({} undecodable)", self.undecodable)?;
+        }
+        Ok(())
+    }
+}
+
+/// Decodes the pending extrinsics and summarises them
+///
+/// # Arguments
+///
+/// * `extrinsics` - The hex encoded extrinsics from `author_pendingExtrinsics`
+/// * `metadata` - The node's metadata
+/// * `own` - Our coldkey, whose extrinsics are not reported as competing
+/// * `target_netuid` - The netuid our subnet will get, if known
+///
+/// # Returns
+///
+/// A `Result` containing the `PendingSummary`, or an `Err` if the metadata lacks the extrinsic
+/// types. Extrinsics that cannot be decoded are only counted.
+pub fn summarise(
+    extrinsics: &[String],
+    metadata: Metadata,
+    own: &AccountId32,
+    target_netuid: Option<u16>,
+) -> Result<PendingSummary, Box<dyn std::error::Error>> {
+    let mut summary = PendingSummary {
+        total: extrinsics.len(),
+        ..Default::default()
+    };
+    for extrinsic in extrinsics {
+        // Each extrinsic is decoded on its own, so a malformed one does not hide the rest
+        let Ok(bytes) = from_hex(extrinsic) else {
+            summary.undecodable += 1;
+            continue;
+        };
+        let decoded =
+            subxt_core::blocks::decode_from::<SubstrateConfig>(vec![bytes], metadata.clone())?;
+        let Some(extrinsic) = decoded
+            .iter()
+            .next()
+            .and_then(Result::ok)
+            .and_then(|details| decode(&details))
+        else {
+            summary.undecodable += 1;
+            continue;
+        };
+        *summary
+            .calls
+            .entry(format!("{}::{}", extrinsic.pallet, extrinsic.call))
+            .or_default() += 1;
+        if extrinsic.is_competing(own, target_netuid) {
+            summary.competing.push(extrinsic);
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Reads the signer, call, key arguments, tip and nonce of an extrinsic, `None` if its call
+/// does not match the metadata
+fn decode(details: &ExtrinsicDetails<SubstrateConfig>) -> Option<PendingExtrinsic> {
+    let signer = details
+        .address_bytes()
+        .and_then(|mut address| MultiAddress::<AccountId32, u32>::decode(&mut address).ok())
+        .and_then(|address| match address {
+            MultiAddress::Id(account) => Some(account),
+            _ => None,
+        });
+    let extensions = details.signed_extensions();
+    let fields = details.field_values().ok()?;
+
+    Some(PendingExtrinsic {
+        signer,
+        pallet: details.pallet_name().ok()?.to_string(),
+        call: details.variant_name().ok()?.to_string(),
+        netuid: field(&fields, "netuid")
+            .and_then(Value::as_u128)
+            .and_then(|netuid| u16::try_from(netuid).ok()),
+        hotkey: field(&fields, "hotkey").and_then(account),
+        tip_rao: extensions.as_ref().and_then(|extensions| extensions.tip()),
+        nonce: extensions
+            .as_ref()
+            .and_then(|extensions| extensions.nonce()),
+    })
+}
+
+/// Returns the named argument of a call
+fn field<'a>(fields: &'a Composite<u32>, name: &str) -> Option<&'a Value<u32>> {
+    match fields {
+        Composite::Named(fields) => fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value),
+        Composite::Unnamed(_) => None,
+    }
+}
+
+/// Reads an `AccountId32` argument, which decodes as nested composites of 32 bytes
+fn account(value: &Value<u32>) -> Option<AccountId32> {
+    fn collect(value: &Value<u32>, bytes: &mut Vec<u8>) {
+        match &value.value {
+            ValueDef::Composite(composite) => {
+                composite.values().for_each(|value| collect(value, bytes))
+            }
+            ValueDef::Primitive(Primitive::U128(byte)) => bytes.push(*byte as u8),
+            _ => {}
+        }
+    }
+
+    let mut bytes = Vec::with_capacity(32);
+    collect(value, &mut bytes);
+    Some(AccountId32(bytes.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::{plan_registration, IdentityParams};
+    use subxt::client::{ClientState, RuntimeVersion};
+    use subxt::config::DefaultExtrinsicParamsBuilder;
+    use subxt::ext::sp_core::bytes::to_hex;
+    use subxt::ext::sp_core::{sr25519, Pair};
+    use subxt::tx::{PairSigner, Payload};
+    use subxt::utils::H256;
+
+    fn metadata() -> Metadata {
+        Metadata::decode(&mut &include_bytes!("../metadata.scale")[..]).unwrap()
+    }
+
+    fn signer(seed: &str) -> PairSigner<SubstrateConfig, sr25519::Pair> {
+        PairSigner::new(sr25519::Pair::from_string(seed, None).unwrap())
+    }
+
+    /// Signs `call` and returns it hex encoded, as `author_pendingExtrinsics` does
+    fn signed(
+        metadata: &Metadata,
+        call: &impl Payload,
+        signer: &PairSigner<SubstrateConfig, sr25519::Pair>,
+        tip_rao: u128,
+        nonce: u64,
+    ) -> String {
+        let state = ClientState::<SubstrateConfig> {
+            genesis_hash: H256::zero(),
+            runtime_version: RuntimeVersion {
+                spec_version: 1,
+                transaction_version: 1,
+            },
+            metadata: metadata.clone(),
+        };
+        let params = DefaultExtrinsicParamsBuilder::new()
+            .tip(tip_rao)
+            .nonce(nonce)
+            .build();
+        let extrinsic = subxt_core::tx::create_signed(call, &state, signer, params).unwrap();
+        to_hex(extrinsic.encoded(), false)
+    }
+
+    fn register_network(
+        metadata: &Metadata,
+        signer: &PairSigner<SubstrateConfig, sr25519::Pair>,
+    ) -> String {
+        let plan = plan_registration(
+            metadata,
+            signer.account_id(),
+            None,
+            &IdentityParams::default(),
+        )
+        .unwrap();
+        signed(metadata, &plan.call, signer, 5, 7)
+    }
+
+    fn burned_register(
+        metadata: &Metadata,
+        signer: &PairSigner<SubstrateConfig, sr25519::Pair>,
+        netuid: u16,
+        hotkey: &AccountId32,
+    ) -> String {
+        let call = subxt::dynamic::tx(
+            "SubtensorModule",
+            "burned_register",
+            vec![Value::u128(netuid.into()), Value::from_bytes(hotkey.0)],
+        );
+        signed(metadata, &call, signer, 0, 1)
+    }
+
+    #[test]
+    fn signed_extrinsics_are_decoded() {
+        let metadata = metadata();
+        let other = signer("//Alice");
+        let hotkey = signer("//Bob").account_id().clone();
+        let extrinsics = [
+            register_network(&metadata, &other),
+            burned_register(&metadata, &other, 3, &hotkey),
+        ];
+
+        let summary = summarise(&extrinsics, metadata, &AccountId32([0; 32]), None).unwrap();
+        assert_eq!(summary.total, 2);
+        assert_eq!(summary.undecodable, 0);
+        assert_eq!(summary.calls["SubtensorModule::register_network"], 1);
+        assert_eq!(summary.calls["SubtensorModule::burned_register"], 1);
+
+        let registration = &summary.competing[0];
+        assert_eq!(
+            registration
+                .signer
+                .as_ref()
+                .map(ToString::to_string)
+                .as_deref(),
+            Some("5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY")
+        );
+        assert_eq!(registration.tip_rao, Some(5));
+        assert_eq!(registration.nonce, Some(7));
+    }
+
+    #[test]
+    fn burned_register_competes_only_on_the_target_netuid() {
+        let metadata = metadata();
+        let own = signer("//Alice");
+        let other = signer("//Charlie");
+        let hotkey = signer("//Bob").account_id().clone();
+        let extrinsics = [
+            register_network(&metadata, &own),
+            burned_register(&metadata, &other, 3, &hotkey),
+            burned_register(&metadata, &other, 4, &hotkey),
+        ];
+
+        let summary = summarise(&extrinsics, metadata, own.account_id(), Some(3)).unwrap();
+        assert_eq!(summary.competing.len(), 1);
+        let competing = &summary.competing[0];
+        assert_eq!(competing.call, "burned_register");
+        assert_eq!(competing.signer.as_ref(), Some(other.account_id()));
+        assert_eq!(competing.netuid, Some(3));
+        assert_eq!(competing.hotkey.as_ref(), Some(&hotkey));
+    }
+
+    #[test]
+    fn malformed_extrinsics_do_not_hide_the_rest() {
+        let metadata = metadata();
+        let other = signer("//Alice");
+        let extrinsics = [
+            register_network(&metadata, &other),
+            "0x1234".to_string(),
+            "not hex".to_string(),
+            register_network(&metadata, &other),
+        ];
+
+        let summary = summarise(&extrinsics, metadata, &AccountId32([0; 32]), None).unwrap();
+        assert_eq!(summary.total, 4);
+        assert_eq!(summary.undecodable, 2);
+        assert_eq!(summary.calls["SubtensorModule::register_network"], 2);
+        assert_eq!(summary.competing.len(), 2);
+    }
+}
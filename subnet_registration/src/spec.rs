@@ -0,0 +1,356 @@
+//! Declarative subnet hyperparameters.
+//!
+//! A spec file such as `subnet.toml` lists the desired hyperparameters of a subnet. Each one is
+//! compared with the value in `SubtensorModule` storage, and only the values that differ are set,
+//! one `AdminUtils::sudo_set_*` extrinsic each, signed by the subnet owner's coldkey. Applying the
+//! same spec again finds nothing to change.
+
+use crate::runtime;
+use crate::runtime::admin_utils::calls::types;
+use log::{error, info};
+use serde::{de, Deserialize, Deserializer};
+use shared::tx::tao_to_rao;
+use std::fmt::Display;
+use std::fs;
+use subxt::ext::sp_core::sr25519;
+use subxt::storage::{Address, Storage};
+use subxt::tx::{DefaultPayload, PairSigner, Payload};
+use subxt::utils::Yes;
+use subxt::{OnlineClient, SubstrateConfig};
+
+/// Desired hyperparameters of a subnet, unset values are left as they are
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct SubnetSpec {
+    pub tempo: Option<u16>,
+    pub immunity_period: Option<u16>,
+    /// Lowest burn in RAO, written in TAO as `min_burn_tao`
+    #[serde(default, rename = "min_burn_tao", deserialize_with = "tao")]
+    pub min_burn: Option<u64>,
+    /// Highest burn in RAO, written in TAO as `max_burn_tao`
+    #[serde(default, rename = "max_burn_tao", deserialize_with = "tao")]
+    pub max_burn: Option<u64>,
+    pub difficulty: Option<u64>,
+    pub min_difficulty: Option<u64>,
+    pub max_difficulty: Option<u64>,
+    pub max_allowed_uids: Option<u16>,
+    pub max_allowed_validators: Option<u16>,
+    pub max_registrations_per_block: Option<u16>,
+    pub target_registrations_per_interval: Option<u16>,
+    pub adjustment_interval: Option<u16>,
+    pub adjustment_alpha: Option<u64>,
+    pub registration_allowed: Option<bool>,
+    pub pow_registration_allowed: Option<bool>,
+    pub weights_set_rate_limit: Option<u64>,
+    pub weights_version_key: Option<u64>,
+    pub min_allowed_weights: Option<u16>,
+    pub max_weight_limit: Option<u16>,
+    pub activity_cutoff: Option<u16>,
+    pub serving_rate_limit: Option<u64>,
+    pub kappa: Option<u16>,
+    pub rho: Option<u16>,
+    pub bonds_moving_average: Option<u64>,
+    pub commit_reveal_weights_enabled: Option<bool>,
+    pub commit_reveal_weights_interval: Option<u64>,
+    pub liquid_alpha_enabled: Option<bool>,
+}
+
+impl SubnetSpec {
+    /// Reads a spec from a TOML file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The spec file
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `SubnetSpec`, or an `Err` if the file cannot be read or holds an
+    /// unknown or invalid value
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let spec =
+            toml::from_str(&contents).map_err(|e| format!("Invalid spec {}: {}", path, e))?;
+
+        Ok(spec)
+    }
+}
+
+/// Reads a TAO amount of the spec as RAO
+fn tao<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    let amount = String::deserialize(deserializer)?;
+    tao_to_rao(&amount).map(Some).map_err(de::Error::custom)
+}
+
+/// Declares the hyperparameters of a spec
+///
+/// Each line names the spec field, the `SubtensorModule` storage entry holding the current
+/// value, the `AdminUtils` call setting it with its generated payload type, and how the value is
+/// logged. This generates `SetCall`, holding the generated payload of any of the calls, and
+/// `diff_spec`, which compares every field with its storage entry.
+macro_rules! hyperparameters {
+    ($($field:ident => $storage:ident, $call:ident($payload:ident), $show:ident;)*) => {
+        /// The generated payload of the call setting one hyperparameter
+        // Variants are named after the generated payload types, which all start with `SudoSet`
+        #[allow(clippy::enum_variant_names)]
+        enum SetCall {
+            $($payload(DefaultPayload<types::$payload>),)*
+        }
+
+        impl SetCall {
+            /// Submits the call and waits for it to succeed in a finalized block
+            async fn submit(
+                &self,
+                client: &OnlineClient<SubstrateConfig>,
+                signer: &PairSigner<SubstrateConfig, sr25519::Pair>,
+            ) -> Result<(), subxt::Error> {
+                match self {
+                    $(SetCall::$payload(call) => submit(client, signer, call).await,)*
+                }
+            }
+        }
+
+        /// Reads the current hyperparameters of `netuid` and builds a `Change` for every value
+        /// that differs from `spec`
+        async fn diff_spec(
+            client: &OnlineClient<SubstrateConfig>,
+            netuid: u16,
+            spec: &SubnetSpec,
+        ) -> Result<Vec<Change>, Box<dyn std::error::Error>> {
+            let storage = client.storage().at_latest().await?;
+            let values = runtime::storage().subtensor_module();
+            let calls = runtime::tx().admin_utils();
+
+            let mut changes = Vec::new();
+            $(
+                let change = diff(
+                    &storage,
+                    stringify!($field),
+                    spec.$field,
+                    values.$storage(netuid),
+                    $show,
+                    |value| SetCall::$payload(calls.$call(netuid, value)),
+                )
+                .await?;
+                changes.extend(change);
+            )*
+
+            Ok(changes)
+        }
+    };
+}
+
+hyperparameters! {
+    tempo => tempo, sudo_set_tempo(SudoSetTempo), plain;
+    immunity_period => immunity_period, sudo_set_immunity_period(SudoSetImmunityPeriod), plain;
+    min_burn => min_burn, sudo_set_min_burn(SudoSetMinBurn), rao;
+    max_burn => max_burn, sudo_set_max_burn(SudoSetMaxBurn), rao;
+    difficulty => difficulty, sudo_set_difficulty(SudoSetDifficulty), plain;
+    min_difficulty => min_difficulty, sudo_set_min_difficulty(SudoSetMinDifficulty), plain;
+    max_difficulty => max_difficulty, sudo_set_max_difficulty(SudoSetMaxDifficulty), plain;
+    max_allowed_uids => max_allowed_uids, sudo_set_max_allowed_uids(SudoSetMaxAllowedUids), plain;
+    max_allowed_validators => max_allowed_validators,
+        sudo_set_max_allowed_validators(SudoSetMaxAllowedValidators), plain;
+    max_registrations_per_block => max_registrations_per_block,
+        sudo_set_max_registrations_per_block(SudoSetMaxRegistrationsPerBlock), plain;
+    target_registrations_per_interval => target_registrations_per_interval,
+        sudo_set_target_registrations_per_interval(SudoSetTargetRegistrationsPerInterval), plain;
+    adjustment_interval => adjustment_interval,
+        sudo_set_adjustment_interval(SudoSetAdjustmentInterval), plain;
+    adjustment_alpha => adjustment_alpha, sudo_set_adjustment_alpha(SudoSetAdjustmentAlpha), plain;
+    registration_allowed => network_registration_allowed,
+        sudo_set_network_registration_allowed(SudoSetNetworkRegistrationAllowed), plain;
+    pow_registration_allowed => network_pow_registration_allowed,
+        sudo_set_network_pow_registration_allowed(SudoSetNetworkPowRegistrationAllowed), plain;
+    weights_set_rate_limit => weights_set_rate_limit,
+        sudo_set_weights_set_rate_limit(SudoSetWeightsSetRateLimit), plain;
+    weights_version_key => weights_version_key,
+        sudo_set_weights_version_key(SudoSetWeightsVersionKey), plain;
+    min_allowed_weights => min_allowed_weights,
+        sudo_set_min_allowed_weights(SudoSetMinAllowedWeights), plain;
+    max_weight_limit => max_weights_limit, sudo_set_max_weight_limit(SudoSetMaxWeightLimit), plain;
+    activity_cutoff => activity_cutoff, sudo_set_activity_cutoff(SudoSetActivityCutoff), plain;
+    serving_rate_limit => serving_rate_limit,
+        sudo_set_serving_rate_limit(SudoSetServingRateLimit), plain;
+    kappa => kappa, sudo_set_kappa(SudoSetKappa), plain;
+    rho => rho, sudo_set_rho(SudoSetRho), plain;
+    bonds_moving_average => bonds_moving_average,
+        sudo_set_bonds_moving_average(SudoSetBondsMovingAverage), plain;
+    commit_reveal_weights_enabled => commit_reveal_weights_enabled,
+        sudo_set_commit_reveal_weights_enabled(SudoSetCommitRevealWeightsEnabled), plain;
+    commit_reveal_weights_interval => weight_commit_reveal_interval,
+        sudo_set_commit_reveal_weights_interval(SudoSetCommitRevealWeightsInterval), plain;
+    liquid_alpha_enabled => liquid_alpha_on,
+        sudo_set_liquid_alpha_enabled(SudoSetLiquidAlphaEnabled), plain;
+}
+
+/// Hyperparameters the runtime keeps ordered as `(min, max)`
+const BOUNDS: [(&str, &str); 2] = [
+    ("min_burn", "max_burn"),
+    ("min_difficulty", "max_difficulty"),
+];
+
+/// A hyperparameter whose current value differs from the spec, with the call that sets it
+struct Change {
+    name: &'static str,
+    current: String,
+    desired: String,
+    /// Whether the desired value is above the current one
+    raises: bool,
+    call: SetCall,
+}
+
+/// Compares the current values of `netuid` with `spec` and sets the ones that differ
+///
+/// Every differing value is logged as `current -> desired` before anything is submitted. The
+/// extrinsics are submitted one at a time, so a value the coldkey may not set (most runtimes
+/// reserve some hyperparameters for root) fails on its own without holding back the others.
+///
+/// # Arguments
+///
+/// * `client` - A reference to the blockchain client
+/// * `signer` - The subnet owner's coldkey
+/// * `netuid` - The subnet to configure
+/// * `spec` - The desired hyperparameters
+/// * `dry_run` - Only log the differences, submit nothing
+///
+/// # Returns
+///
+/// A `Result` which is `Ok` if the subnet matches the spec (or would be changed, for a dry run),
+/// or an `Err` naming the values that could not be set
+pub async fn apply_spec(
+    client: &OnlineClient<SubstrateConfig>,
+    signer: &PairSigner<SubstrateConfig, sr25519::Pair>,
+    netuid: u16,
+    spec: &SubnetSpec,
+    dry_run: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut changes = diff_spec(client, netuid, spec).await?;
+    if changes.is_empty() {
+        info!("Subnet {} already matches the spec", netuid);
+        return Ok(());
+    }
+    order_bounds(&mut changes);
+
+    info!("Subnet {} differs from the spec:", netuid);
+    for change in &changes {
+        info!(
+            "  {}: {} -> {}",
+            change.name, change.current, change.desired
+        );
+    }
+    if dry_run {
+        info!("Dry run: the hyperparameters will not be set.");
+        return Ok(());
+    }
+
+    let mut failed = Vec::new();
+    for change in &changes {
+        match change.call.submit(client, signer).await {
+            Ok(()) => info!("Set {} to {}", change.name, change.desired),
+            Err(e) => {
+                error!("Failed to set {} to {}: {}", change.name, change.desired, e);
+                failed.push(change.name);
+            }
+        }
+    }
+
+    if failed.is_empty() {
+        info!("Subnet {} now matches the spec", netuid);
+        Ok(())
+    } else {
+        Err(format!(
+            "{} of {} hyperparameters could not be set: {}",
+            failed.len(),
+            changes.len(),
+            failed.join(", ")
+        )
+        .into())
+    }
+}
+
+/// Orders the changes of every `(min, max)` pair so the bounds never cross in between
+///
+/// When the maximum is raised it is set before the minimum, so a new minimum above the old
+/// maximum is accepted. When it is lowered, the minimum is set first.
+fn order_bounds(changes: &mut [Change]) {
+    for (min, max) in BOUNDS {
+        let min = changes.iter().position(|change| change.name == min);
+        let max = changes.iter().position(|change| change.name == max);
+        if let (Some(min), Some(max)) = (min, max) {
+            if changes[max].raises == (max > min) {
+                changes.swap(min, max);
+            }
+        }
+    }
+}
+
+/// Compares one desired value with its storage entry
+///
+/// # Arguments
+///
+/// * `storage` - The storage to read the current value from
+/// * `name` - The hyperparameter's name in the spec
+/// * `desired` - The value from the spec, `None` if the spec leaves it as it is
+/// * `address` - The storage entry holding the current value
+/// * `show` - Formats a value for the log
+/// * `call` - Builds the call setting the value
+///
+/// # Returns
+///
+/// A `Result` containing the `Change` if the value differs, `None` otherwise, or an `Err` if
+/// the storage cannot be read
+async fn diff<T, A>(
+    storage: &Storage<SubstrateConfig, OnlineClient<SubstrateConfig>>,
+    name: &'static str,
+    desired: Option<T>,
+    address: A,
+    show: fn(T) -> String,
+    call: impl FnOnce(T) -> SetCall,
+) -> Result<Option<Change>, Box<dyn std::error::Error>>
+where
+    T: PartialOrd + Copy,
+    A: Address<Target = T, IsFetchable = Yes, IsDefaultable = Yes>,
+{
+    let Some(desired) = desired else {
+        return Ok(None);
+    };
+    let current = storage.fetch_or_default(&address).await?;
+    if current == desired {
+        return Ok(None);
+    }
+
+    Ok(Some(Change {
+        name,
+        current: show(current),
+        desired: show(desired),
+        raises: desired > current,
+        call: call(desired),
+    }))
+}
+
+/// Submits `call` and waits for it to succeed in a finalized block
+async fn submit<C: Payload>(
+    client: &OnlineClient<SubstrateConfig>,
+    signer: &PairSigner<SubstrateConfig, sr25519::Pair>,
+    call: &C,
+) -> Result<(), subxt::Error> {
+    client
+        .tx()
+        .sign_and_submit_then_watch_default(call, signer)
+        .await?
+        .wait_for_finalized_success()
+        .await?;
+
+    Ok(())
+}
+
+/// Formats a plain value
+fn plain<T: Display>(value: T) -> String {
+    value.to_string()
+}
+
+/// Formats an amount in RAO as TAO
+fn rao(rao: u64) -> String {
+    format!("{:.9} TAO", rao as f64 / 1e9)
+}
@@ -0,0 +1,164 @@
+//! Signing and tracking of the `register_network` extrinsic.
+//!
+//! The lock cost is taken when the extrinsic lands, so a registration whose outcome was lost with
+//! the connection must not be signed again while it can still land. The extrinsic is signed with
+//! an explicit nonce and a short mortality, and after a reconnect its validity window is scanned
+//! for the extrinsic hash. It is only given up once its nonce is used by another extrinsic or the
+//! window has passed.
+
+use log::info;
+use shared::tx::find_extrinsic;
+use subxt::backend::legacy::LegacyRpcMethods;
+use subxt::backend::rpc::RpcClient;
+use subxt::blocks::ExtrinsicEvents;
+use subxt::config::substrate::SubstrateExtrinsicParamsBuilder;
+use subxt::error::DispatchError;
+use subxt::ext::sp_core::sr25519;
+use subxt::tx::{PairSigner, Payload, SubmittableExtrinsic};
+use subxt::utils::H256;
+use subxt::{OnlineClient, SubstrateConfig};
+
+/// Number of blocks the extrinsic stays valid for, rounded by the runtime to a power of two
+const MORTALITY_BLOCKS: u64 = 16;
+
+/// A signed registration extrinsic that may still land
+pub struct Submission {
+    /// Hash of the extrinsic
+    pub extrinsic_hash: H256,
+    nonce: u64,
+    /// First block not scanned for the extrinsic yet
+    next_block: u64,
+    /// First block the extrinsic is no longer valid in
+    valid_until: u64,
+}
+
+/// Where a submission ended up
+pub enum Resolution {
+    /// Included in a finalized block, with the block hash and the events of the extrinsic
+    Included(H256, ExtrinsicEvents<SubstrateConfig>),
+    /// Not included yet, but it still can be
+    Pending,
+    /// The nonce was used by another extrinsic or the validity window passed
+    Gone,
+}
+
+impl Submission {
+    /// Signs `call` with the current account nonce and a mortality anchored at the latest
+    /// finalized block
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - A reference to the blockchain client
+    /// * `call` - The registration call
+    /// * `signer` - The coldkey paying for the registration
+    /// * `tip_rao` - The tip to sign the extrinsic with
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Submission` and the extrinsic to submit, or an `Err` if the
+    /// nonce cannot be fetched or the call cannot be signed
+    pub async fn sign<Call: Payload>(
+        client: &OnlineClient<SubstrateConfig>,
+        call: &Call,
+        signer: &PairSigner<SubstrateConfig, sr25519::Pair>,
+        tip_rao: u64,
+    ) -> Result<
+        (
+            Self,
+            SubmittableExtrinsic<SubstrateConfig, OnlineClient<SubstrateConfig>>,
+        ),
+        Box<dyn std::error::Error>,
+    > {
+        let header = client.blocks().at_latest().await?.header().clone();
+        let nonce = client.tx().account_nonce(signer.account_id()).await?;
+        let params = SubstrateExtrinsicParamsBuilder::new()
+            .tip(tip_rao as u128)
+            .nonce(nonce)
+            .mortal(&header, MORTALITY_BLOCKS)
+            .build();
+        let extrinsic = client.tx().create_signed_offline(call, signer, params)?;
+        let block_number = u64::from(header.number);
+
+        Ok((
+            Self {
+                extrinsic_hash: extrinsic.hash(),
+                nonce,
+                next_block: block_number,
+                valid_until: block_number + MORTALITY_BLOCKS,
+            },
+            extrinsic,
+        ))
+    }
+
+    /// Looks the extrinsic up in the finalized blocks of its validity window
+    ///
+    /// Blocks already scanned by a previous call are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - A reference to the blockchain client
+    /// * `rpc` - The RPC client, used to look up block hashes by number
+    /// * `signer` - The coldkey that signed the extrinsic
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Resolution`, or an `Err` if the chain cannot be queried
+    pub async fn resolve(
+        &mut self,
+        client: &OnlineClient<SubstrateConfig>,
+        rpc: &RpcClient,
+        signer: &PairSigner<SubstrateConfig, sr25519::Pair>,
+    ) -> Result<Resolution, Box<dyn std::error::Error>> {
+        let rpc_methods = LegacyRpcMethods::<SubstrateConfig>::new(rpc.clone());
+        let head = client.blocks().at_latest().await?;
+        let head_number = u64::from(head.number());
+
+        let last_block = head_number.min(self.valid_until - 1);
+        while self.next_block <= last_block {
+            if let Some((block_hash, events)) =
+                find_extrinsic(client, &rpc_methods, self.next_block, self.extrinsic_hash).await?
+            {
+                info!(
+                    "Extrinsic {:?} was included at block {}",
+                    self.extrinsic_hash, self.next_block
+                );
+                return Ok(Resolution::Included(block_hash, events));
+            }
+            self.next_block += 1;
+        }
+
+        if head_number + 1 >= self.valid_until
+            || head.account_nonce(signer.account_id()).await? > self.nonce
+        {
+            return Ok(Resolution::Gone);
+        }
+
+        Ok(Resolution::Pending)
+    }
+}
+
+/// Checks the events of an included extrinsic for `System::ExtrinsicFailed`
+///
+/// # Arguments
+///
+/// * `client` - A reference to the blockchain client, whose metadata decodes the error
+/// * `events` - The events of the extrinsic
+///
+/// # Returns
+///
+/// A `Result` containing the events, or an `Err` with the dispatch error if the extrinsic failed
+pub fn check_success(
+    client: &OnlineClient<SubstrateConfig>,
+    events: ExtrinsicEvents<SubstrateConfig>,
+) -> Result<ExtrinsicEvents<SubstrateConfig>, subxt::Error> {
+    for event in events.iter() {
+        let event = event?;
+        if event.pallet_name() == "System" && event.variant_name() == "ExtrinsicFailed" {
+            let dispatch_error =
+                DispatchError::decode_from(event.field_bytes(), client.metadata())?;
+            return Err(dispatch_error.into());
+        }
+    }
+
+    Ok(events)
+}
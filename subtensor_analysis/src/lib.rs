@@ -26,16 +26,16 @@
 //! }
 //! ```
 
-use log::info;
+use log::{info, warn};
 use polars::prelude::*;
-use shared::estimate_block_time;
+use shared::errors::Error;
+use shared::resilient::{is_connection_error, ResilientClient};
 use std::path::PathBuf;
 use std::time::{Duration, Instant};
-use subxt::backend::rpc::{rpc_params, RpcClient};
+use subxt::backend::rpc::rpc_params;
 use subxt::dynamic::Value;
 use subxt::ext::scale_value::{Composite, ValueDef};
 use subxt::utils::H256;
-use subxt::{OnlineClient, SubstrateConfig};
 
 /// Configuration for the Subtensor analysis
 #[derive(Clone, Debug)]
@@ -46,8 +46,6 @@ pub struct AnalysisConfig {
     pub output_dir: String,
 }
 
-/// Main struct for analyzing Subtensor blockchain data
-
 /// Struct for analyzing Subtensor blockchain data
 ///
 /// This struct provides methods to interact with the Subtensor blockchain,
@@ -56,7 +54,7 @@ pub struct AnalysisConfig {
 ///
 /// # Example
 ///
-/// ```rust
+/// ```rust,no_run
 /// use subtensor_analysis::{SubtensorAnalyzer, AnalysisConfig};
 ///
 /// #[tokio::main]
@@ -72,10 +70,8 @@ pub struct AnalysisConfig {
 /// ```
 #[derive(Clone)]
 pub struct SubtensorAnalyzer {
-    /// Client for interacting with the Subtensor blockchain
-    client: OnlineClient<SubstrateConfig>,
-    /// RPC client for making direct RPC calls to the Subtensor node
-    rpc_client: RpcClient,
+    /// Reconnecting connection to the Subtensor blockchain, used for subscriptions and direct RPC calls
+    connection: ResilientClient,
     /// Directory path for storing output files
     output_dir: PathBuf,
 }
@@ -87,15 +83,13 @@ pub struct SubtensorAnalyzer {
 impl SubtensorAnalyzer {
     /// Create a new SubtensorAnalyzer instance
     pub async fn new(config: AnalysisConfig) -> Result<Self, Box<dyn std::error::Error>> {
-        let client = OnlineClient::<SubstrateConfig>::from_url(&config.chain_endpoint).await?;
-        let rpc_client = RpcClient::from_url(&config.chain_endpoint).await?;
+        let connection = ResilientClient::connect(&config.chain_endpoint).await?;
         let output_dir = PathBuf::from(config.output_dir);
 
         std::fs::create_dir_all(&output_dir)?;
 
         Ok(Self {
-            client,
-            rpc_client,
+            connection,
             output_dir,
         })
     }
@@ -106,36 +100,55 @@ impl SubtensorAnalyzer {
         let mut block_weights = Vec::new();
         let mut block_numbers = Vec::new();
 
-        let mut blocks = self.client.blocks().subscribe_finalized().await?;
+        let mut blocks = self
+            .connection
+            .client()
+            .blocks()
+            .subscribe_finalized()
+            .await?;
         let mut last_block_time: Option<Instant> = None;
-
-        for _ in 0..num_blocks {
-            if let Some(block) = blocks.next().await {
-                let block = block?;
-                let block_number = block.header().number;
-                block_numbers.push(block_number);
-
-                // Calculate block time
-                let current_time = Instant::now();
-                if let Some(last_time) = last_block_time {
-                    let block_time = current_time.duration_since(last_time).as_secs_f64();
-                    block_times.push(block_time);
+        let mut collected = 0;
+
+        while collected < num_blocks {
+            let sample = match blocks.next().await {
+                Some(Ok(block)) => self.sample_block(block.header().number).await,
+                Some(Err(e)) => Err(e.into()),
+                None => Err(Error::Disconnected("Block subscription ended".to_string()).into()),
+            };
+
+            let (block_number, pending_extrinsics, block_weight) = match sample {
+                Ok(sample) => sample,
+                Err(e) if is_connection_error(e.as_ref()) => {
+                    // Keep the data collected so far and resume on a fresh subscription. The
+                    // time across the gap is not a block time, so the next block starts over.
+                    warn!("🔌 Lost the connection while collecting data: {}", e);
+                    self.connection.reconnect().await;
+                    blocks = self
+                        .connection
+                        .client()
+                        .blocks()
+                        .subscribe_finalized()
+                        .await?;
+                    last_block_time = None;
+                    continue;
                 }
-                last_block_time = Some(current_time);
-
-                // Collect pending extrinsics count
-                let pending_extrinsics: Vec<String> = self
-                    .rpc_client
-                    .request("author_pendingExtrinsics", rpc_params![])
-                    .await?;
-                pending_extrinsics_counts.push(pending_extrinsics.len() as u32);
+                Err(e) => return Err(e),
+            };
+            collected += 1;
+            block_numbers.push(block_number);
+
+            // Calculate block time
+            let current_time = Instant::now();
+            if let Some(last_time) = last_block_time {
+                let block_time = current_time.duration_since(last_time).as_secs_f64();
+                block_times.push(block_time);
+            }
+            last_block_time = Some(current_time);
 
-                // Collect block weight
-                let block_weight = self.get_block_weight(block_number).await?;
-                block_weights.push(block_weight);
+            pending_extrinsics_counts.push(pending_extrinsics);
+            block_weights.push(block_weight);
 
-                info!("Collected data for block {}", block_number);
-            }
+            info!("Collected data for block {}", block_number);
         }
 
         // Ensure all vectors have the same length
@@ -162,6 +175,35 @@ impl SubtensorAnalyzer {
         Ok(())
     }
 
+    /// Collect the pending extrinsics count and the weight of a finalized block
+    ///
+    /// Both values are fetched before anything is recorded, so a connection dropping halfway
+    /// through does not leave the collected series with different lengths.
+    ///
+    /// # Arguments
+    ///
+    /// * `block_number` - The number of the finalized block
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the block number, the pending extrinsics count and the block weight
+    async fn sample_block(
+        &self,
+        block_number: u32,
+    ) -> Result<(u32, u32, u64), Box<dyn std::error::Error>> {
+        // Collect pending extrinsics count
+        let pending_extrinsics: Vec<String> = self
+            .connection
+            .rpc()
+            .request("author_pendingExtrinsics", rpc_params![])
+            .await?;
+
+        // Collect block weight
+        let block_weight = self.get_block_weight(block_number).await?;
+
+        Ok((block_number, pending_extrinsics.len() as u32, block_weight))
+    }
+
     /// Analyze block times and provide insights
     pub fn analyze_block_times(&self) -> Result<(), Box<dyn std::error::Error>> {
         let input_file = self.output_dir.join("block_data.parquet");
@@ -199,7 +241,7 @@ impl SubtensorAnalyzer {
     ///
     /// # Example
     ///
-    /// ```no_run
+    /// ```
     /// # use subtensor_analysis::{SubtensorAnalyzer, AnalysisConfig};
     /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
     /// # let config = AnalysisConfig {
@@ -212,10 +254,14 @@ impl SubtensorAnalyzer {
     /// # Ok(())
     /// # }
     /// ```
-    async fn get_block_weight(&self, block_number: u32) -> Result<u64, Box<dyn std::error::Error>> {
+    pub async fn get_block_weight(
+        &self,
+        block_number: u32,
+    ) -> Result<u64, Box<dyn std::error::Error>> {
         // Get the block hash for the given block number
         let block_hash: Option<H256> = self
-            .rpc_client
+            .connection
+            .rpc()
             .request("chain_getBlockHash", rpc_params![block_number])
             .await?;
 
@@ -223,7 +269,8 @@ impl SubtensorAnalyzer {
 
         // Get the block using the retrieved hash
         let block: Option<Value> = self
-            .rpc_client
+            .connection
+            .rpc()
             .request("chain_getBlock", rpc_params![block_hash])
             .await?;
 
@@ -320,7 +367,7 @@ impl SubtensorAnalyzer {
 ///
 /// ```
 /// use polars::prelude::*;
-/// use your_crate::calculate_correlation;
+/// use subtensor_analysis::calculate_correlation;
 ///
 /// let df = DataFrame::new(vec![
 ///     Series::new("A", &[1.0, 2.0, 3.0, 4.0, 5.0]),
@@ -330,7 +377,7 @@ impl SubtensorAnalyzer {
 /// let correlation = calculate_correlation(&df, "A", "B").unwrap();
 /// assert_eq!(correlation, 1.0);
 /// ```
-fn calculate_correlation(df: &DataFrame, col1: &str, col2: &str) -> Result<f64, PolarsError> {
+pub fn calculate_correlation(df: &DataFrame, col1: &str, col2: &str) -> Result<f64, PolarsError> {
     // Cast columns to Float64 to ensure compatibility
     let x: Series = df.column(col1)?.cast(&DataType::Float64)?;
     let y: Series = df.column(col2)?.cast(&DataType::Float64)?;
@@ -349,7 +396,7 @@ fn calculate_correlation(df: &DataFrame, col1: &str, col2: &str) -> Result<f64,
     let mut y_variance: f64 = 0.0;
 
     // Iterate over both series simultaneously
-    for (x_val, y_val) in x.f64()?.into_iter().zip(y.f64()?.into_iter()) {
+    for (x_val, y_val) in x.f64()?.into_iter().zip(y.f64()?) {
         if let (Some(x_val), Some(y_val)) = (x_val, y_val) {
             let x_diff: f64 = x_val - x_mean;
             let y_diff: f64 = y_val - y_mean;
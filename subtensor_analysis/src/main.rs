@@ -1,12 +1,11 @@
 use clap::Parser;
 use log::{debug, info, warn};
-use serde::Deserialize;
 use shared::parse_config;
 use std::time::Duration;
 use subtensor_analysis::{AnalysisConfig, SubtensorAnalyzer};
 use tokio::time::sleep;
 
-#[derive(Parser, Debug, Deserialize)]
+#[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Cli {
     #[clap(long, default_value = "ws://127.0.0.1:9944")]